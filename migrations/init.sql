@@ -12,7 +12,8 @@ CREATE TABLE users (
     is_locked BOOLEAN,
     lock_due_at BIGINT,
     is_admin BOOLEAN,
-    last_submission BIGINT
+    last_submission BIGINT,
+    wrong_streak INTEGER DEFAULT 0
 );
 
 CREATE TABLE solve_history (
@@ -21,7 +22,8 @@ CREATE TABLE solve_history (
     username TEXT,
     is_success BOOLEAN,
     time BIGINT,
-    submit_content TEXT
+    submit_content TEXT,
+    stage TEXT
 );
 
 CREATE TABLE challenges (
@@ -31,5 +33,111 @@ CREATE TABLE challenges (
     category TEXT,
     solved_by TEXT[],
     running BOOLEAN,
-    connection_string TEXT
+    connection_string TEXT,
+    start_time BIGINT,
+    end_time BIGINT,
+    scheduled_at BIGINT,
+    flag_rules TEXT,
+    scoring TEXT
+);
+
+CREATE TABLE event_log (
+    id SERIAL PRIMARY KEY,
+    time BIGINT,
+    source TEXT,
+    topic TEXT,
+    payload TEXT
+);
+
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    url TEXT,
+    format TEXT,
+    template TEXT,
+    secret TEXT,
+    topics TEXT[],
+    enabled BOOLEAN
+);
+
+CREATE TABLE webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+    webhook_id INTEGER,
+    time BIGINT,
+    topic TEXT,
+    attempts INTEGER,
+    status_code INTEGER,
+    success BOOLEAN,
+    error TEXT
+);
+
+CREATE TABLE settings (
+    key TEXT PRIMARY KEY,
+    value TEXT
+);
+
+CREATE TABLE flag_reviews (
+    id SERIAL PRIMARY KEY,
+    time BIGINT,
+    challenge_name TEXT,
+    submitted_by TEXT,
+    owner TEXT,
+    flag TEXT
+);
+
+CREATE TABLE challenge_stages (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    name TEXT,
+    weight INTEGER,
+    solved_by TEXT[],
+    UNIQUE (challenge_name, name)
+);
+
+CREATE TABLE solves (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    username TEXT,
+    time BIGINT,
+    position INTEGER,
+    bonus INTEGER,
+    team_id INTEGER,
+    UNIQUE (challenge_name, username)
+);
+
+CREATE UNIQUE INDEX solves_team_unique ON solves (challenge_name, team_id) WHERE team_id > 0;
+
+CREATE TABLE teams (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE,
+    invite_code TEXT UNIQUE,
+    captain TEXT,
+    created_at BIGINT
+);
+
+CREATE TABLE team_members (
+    id SERIAL PRIMARY KEY,
+    team_id INTEGER,
+    username TEXT UNIQUE,
+    joined_at BIGINT
+);
+
+CREATE TABLE hints (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    content TEXT,
+    cost INTEGER,
+    release_after BIGINT
+);
+
+CREATE TABLE hint_unlocks (
+    id SERIAL PRIMARY KEY,
+    hint_id INTEGER,
+    username TEXT,
+    time BIGINT,
+    UNIQUE (hint_id, username)
+);
+
+CREATE TABLE submit_limits (
+    key TEXT PRIMARY KEY,
+    hits BIGINT[]
 );
@@ -0,0 +1,121 @@
+-- Brings a database created by an older init.sql up to date, safe to run more than once:
+-- psql -d livectf -f migrations/upgrade.sql
+
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS start_time BIGINT;
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS end_time BIGINT;
+UPDATE challenges SET start_time = 0 WHERE start_time IS NULL;
+UPDATE challenges SET end_time = 0 WHERE end_time IS NULL;
+UPDATE challenges SET connection_string = '' WHERE connection_string IS NULL;
+
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS scheduled_at BIGINT;
+
+CREATE TABLE IF NOT EXISTS event_log (
+    id SERIAL PRIMARY KEY,
+    time BIGINT,
+    source TEXT,
+    topic TEXT,
+    payload TEXT
+);
+
+CREATE TABLE IF NOT EXISTS webhooks (
+    id SERIAL PRIMARY KEY,
+    url TEXT,
+    format TEXT,
+    template TEXT,
+    secret TEXT,
+    topics TEXT[],
+    enabled BOOLEAN
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+    webhook_id INTEGER,
+    time BIGINT,
+    topic TEXT,
+    attempts INTEGER,
+    status_code INTEGER,
+    success BOOLEAN,
+    error TEXT
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT
+);
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS wrong_streak INTEGER DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS submit_limits (
+    key TEXT PRIMARY KEY,
+    hits BIGINT[]
+);
+
+CREATE TABLE IF NOT EXISTS flag_reviews (
+    id SERIAL PRIMARY KEY,
+    time BIGINT,
+    challenge_name TEXT,
+    submitted_by TEXT,
+    owner TEXT,
+    flag TEXT
+);
+
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS flag_rules TEXT;
+
+ALTER TABLE solve_history ADD COLUMN IF NOT EXISTS stage TEXT;
+UPDATE solve_history SET stage = '' WHERE stage IS NULL;
+
+CREATE TABLE IF NOT EXISTS challenge_stages (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    name TEXT,
+    weight INTEGER,
+    solved_by TEXT[],
+    UNIQUE (challenge_name, name)
+);
+
+CREATE TABLE IF NOT EXISTS hints (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    content TEXT,
+    cost INTEGER,
+    release_after BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS hint_unlocks (
+    id SERIAL PRIMARY KEY,
+    hint_id INTEGER,
+    username TEXT,
+    time BIGINT,
+    UNIQUE (hint_id, username)
+);
+
+CREATE TABLE IF NOT EXISTS solves (
+    id SERIAL PRIMARY KEY,
+    challenge_name TEXT,
+    username TEXT,
+    time BIGINT,
+    UNIQUE (challenge_name, username)
+);
+
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS scoring TEXT;
+
+ALTER TABLE solves ADD COLUMN IF NOT EXISTS position INTEGER;
+ALTER TABLE solves ADD COLUMN IF NOT EXISTS bonus INTEGER;
+
+ALTER TABLE solves ADD COLUMN IF NOT EXISTS team_id INTEGER;
+CREATE UNIQUE INDEX IF NOT EXISTS solves_team_unique ON solves (challenge_name, team_id) WHERE team_id > 0;
+
+CREATE TABLE IF NOT EXISTS teams (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE,
+    invite_code TEXT UNIQUE,
+    captain TEXT,
+    created_at BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS team_members (
+    id SERIAL PRIMARY KEY,
+    team_id INTEGER,
+    username TEXT UNIQUE,
+    joined_at BIGINT
+);
@@ -2,38 +2,16 @@ use actix_web::{web, HttpRequest, HttpResponse, cookie::Cookie};
 use actix_multipart::Multipart;
 use futures_util::{StreamExt, TryStreamExt};
 use uuid::Uuid;
-use std::sync::mpsc::{self, Receiver, Sender};
 use std::io::Write;
 use std::fs::{File, copy};
 use std::collections::BTreeMap;
 
-use crate::notifier::{craft_type_notify_message, Notifier, NotifierCommInfo, NotifierComms};
+use crate::notifier::NotifierComms;
+use crate::deployer::{DeployerCommand, DEPLOYER_REQUEST_TIMEOUT};
+use crate::web_interface::challenge::deployer_reply_to_response;
 use crate::database::{challenge, DbConnection};
 use crate::utils::{is_time_schedule_valid, MAGIC_TIME};
 use crate::web_interface::{get_error, success, get_jwt_claims, forbiden, unauthorized};
-// struct ChallengeUploadHandlerCtx {
-//     sender: Sender<(String, Vec<u8>)>,
-//     listener: Receiver<Vec<u8>>,
-    
-//     db_conn: DbConnection
-// }
-
-pub(crate) fn init(notifier: &mut Notifier, _my_sender: Sender<(String, Vec<u8>)>, _db_conn: DbConnection) {
-    let (notifier_sender, _my_receiver) : (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
-    // let ctx = ChallengeUploadHandlerCtx {
-    //     sender: my_sender,
-    //     listener: my_receiver,
-    //     db_conn
-    // };
-
-
-    let comm_info = NotifierCommInfo {
-        // id: Uuid::new_v4().as_u128(),
-        name: "challenge_upload_handler".to_string(),
-        broadcast_channel: notifier_sender
-    };
-    notifier.slaves.comm_infos.push(comm_info);
-}
 
 pub(crate) async fn handle_challenge(slaves: web::Data<NotifierComms>, db_conn: web::Data<DbConnection>, req: HttpRequest, mut payload: Multipart) -> Result<HttpResponse, actix_web::Error> {
 
@@ -86,10 +64,6 @@ pub(crate) async fn handle_challenge(slaves: web::Data<NotifierComms>, db_conn:
                 f.write_all(&data).unwrap();
             }
 
-            let target_module = String::from("deployer");
-            let data = craft_type_notify_message(&target_module, &["schedule", &filename.to_string(), &start_time.to_string(), &end_time.to_string()]);
-            slaves.notify(target_module, data);
-
             let chall = challenge::ChallengeData {
                 id: 0,
                 challenge_name: filename.to_string(),
@@ -97,14 +71,25 @@ pub(crate) async fn handle_challenge(slaves: web::Data<NotifierComms>, db_conn:
                 category: "Pwn".to_string(),
                 solved_by: Vec::new(),
                 running: false,
-                connection_string: "".to_string()
+                connection_string: "".to_string(),
+                start_time: start_time as i64,
+                end_time: end_time as i64
             };
+            // the row has to exist before the deployer publishes its schedule
             if db_conn.store_challenge_metadata(chall).await {
                 if copy(filepath.to_string(), format!("./attachments/{}.tar.gz", filename))
                     .expect("cannot copy to attachments") == 0 {
                         return Ok(get_error(&format!("Failed to copy to attachments: {}", filepath)));
                 };
-                return Ok(success(&format!("File uploaded successfully: {}", filepath)));
+
+                let reply = slaves.request(|reply| DeployerCommand::Schedule {
+                    challenge_filename: filename.to_string(),
+                    start_time,
+                    end_time,
+                    reply
+                }, DEPLOYER_REQUEST_TIMEOUT).await;
+
+                return Ok(deployer_reply_to_response(reply, &format!("File uploaded successfully: {}", filepath)));
             }
 
             return Ok(success(&format!("Failed to store challenge to database: {}", filename)));
@@ -1,15 +1,10 @@
 use std::vec;
 
-use sqlx::postgres::PgQueryResult;
+use sqlx::postgres::{PgConnection, PgQueryResult};
 use sqlx::FromRow;
 
-use crate::database::{DbConnection, DB_CHALLENGE_TABLE};
-
-const MINIMUM_SCORE: i32 = 50;
-const INITIAL_SCORE: i32 = 500;
-// lower this makes score decay faster
-const DECAY_VALUE: i32 = 300;
-const SOLVES_BEFORE_DECAY: i32 = 1;
+use crate::database::{settings::{get_json_setting, SCORING_SETTING}, DbConnection, DB_CHALLENGE_TABLE};
+use crate::scoring::ScoringConfig;
 
 #[derive(FromRow)]
 pub struct ChallengeData {
@@ -20,7 +15,16 @@ pub struct ChallengeData {
     pub category: String,
     pub solved_by: Vec<String>,
     pub running: bool,
-    pub connection_string: String
+    pub connection_string: String,
+    pub start_time: i64,
+    pub end_time: i64
+}
+
+impl ChallengeData {
+    // running, or waiting for its release. a destroyed challenge ended when it was destroyed, so it isn't
+    pub fn is_pending(&self, now: i64) -> bool {
+        self.running || self.end_time > now
+    }
 }
 
 pub async fn db_store_challenge_metadata(db_connection: &DbConnection, challenge: ChallengeData) -> bool {
@@ -32,7 +36,9 @@ pub async fn db_store_challenge_metadata(db_connection: &DbConnection, challenge
         category,
         solved_by,
         running,
-        connection_string
+        connection_string,
+        start_time,
+        end_time
     )
     VALUES
         (
@@ -41,7 +47,9 @@ pub async fn db_store_challenge_metadata(db_connection: &DbConnection, challenge
             $3,
             $4,
             $5,
-            $6
+            $6,
+            $7,
+            $8
         );", table_name=DB_CHALLENGE_TABLE);
         let result: PgQueryResult = sqlx::query(&query[..])
         .bind(challenge.challenge_name.trim())
@@ -50,6 +58,8 @@ pub async fn db_store_challenge_metadata(db_connection: &DbConnection, challenge
         .bind(no_one_solved)
         .bind(false)
         .bind(challenge.connection_string)
+        .bind(challenge.start_time)
+        .bind(challenge.end_time)
         .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
 
     if result.rows_affected() > 0 {
@@ -58,29 +68,71 @@ pub async fn db_store_challenge_metadata(db_connection: &DbConnection, challenge
     return false;
 } 
 
-pub async fn db_challenge_solve(db_connection: &DbConnection, chall_name: String, username: String) -> bool {
-    let query = format!("UPDATE {table_name} SET solved_by = array_append(solved_by, $2) WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
+// None goes back to the event's scoring
+pub async fn db_set_challenge_scoring(db_connection: &DbConnection, name: String, scoring: Option<ScoringConfig>) -> bool {
+    let query = format!("UPDATE {table_name} SET scoring = $2 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
 
-    let res= sqlx::query(&query[..])
-        .bind(chall_name.trim())
-        .bind(username.trim())
-        .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
-    
-    if res.rows_affected() == 0 {
-        return false;
-    }
+    let res = sqlx::query(&query[..])
+        .bind(name.trim())
+        .bind(scoring.map(|scoring| serde_json::to_string(&scoring).expect("failed serializing scoring")))
+        .execute(&db_connection.pool).await.unwrap_or_default();
 
-    // (((minimum - initial)/(decay**2)) * (solve_count**2)) + initial
-    let chall = db_get_challenge_by_name(db_connection, chall_name).await;
-    let score = (MINIMUM_SCORE - INITIAL_SCORE)/(DECAY_VALUE);
-    let score = score * (i32::try_from(chall.solved_by.len().pow(2)).unwrap() - SOLVES_BEFORE_DECAY);
-    let score = score + INITIAL_SCORE;
+    res.rows_affected() > 0
+}
 
-    if db_update_challenge_score(db_connection, chall.challenge_name, score).await {
-        return true;
-    }
+// sets the score from the solve count and the challenge's scoring, or the event's when it has none, and returns it.
+// the challenge row stays locked until the caller's transaction ends, so concurrent solves can't mix up counts.
+pub async fn update_challenge_score(conn: &mut PgConnection, name: &str) -> Result<Option<i32>, sqlx::Error> {
+    let query = format!("SELECT cardinality(solved_by), scoring FROM {table_name} WHERE challenge_name=$1 FOR UPDATE;", table_name=DB_CHALLENGE_TABLE);
 
-    return false;
+    let row: Option<(Option<i32>, Option<String>)> = sqlx::query_as(&query[..])
+        .bind(name)
+        .fetch_optional(&mut *conn).await?;
+
+    let (solve_count, scoring) = match row {
+        Some(row) => row,
+        None => return Ok(None)
+    };
+
+    let scoring: ScoringConfig = match scoring.and_then(|scoring| serde_json::from_str(&scoring).ok()) {
+        Some(scoring) => scoring,
+        None => get_json_setting(conn, SCORING_SETTING).await?
+    };
+
+    let score = scoring.strategy().points(solve_count.unwrap_or_default());
+    let query = format!("UPDATE {table_name} SET score = $2 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
+    sqlx::query(&query[..])
+        .bind(name)
+        .bind(score)
+        .execute(&mut *conn).await?;
+
+    Ok(Some(score))
+}
+
+pub async fn db_recompute_challenge_score(db_connection: &DbConnection, name: String) -> bool {
+    let recompute = async {
+        let mut tx = db_connection.pool.begin().await?;
+        let score = update_challenge_score(&mut tx, name.trim()).await?;
+        tx.commit().await?;
+        Ok::<bool, sqlx::Error>(score.is_some())
+    };
+
+    recompute.await.unwrap_or(false)
+}
+
+// after the event's scoring changed
+pub async fn db_recompute_all_challenge_scores(db_connection: &DbConnection) -> bool {
+    let query = format!("SELECT challenge_name FROM {table_name};", table_name=DB_CHALLENGE_TABLE);
+
+    let names: Vec<String> = sqlx::query_scalar(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default();
+
+    let mut success = true;
+    for name in names {
+        success &= db_recompute_challenge_score(db_connection, name).await;
+    }
+    success
 }
 
 pub async fn db_get_challenge_by_name(db_connection: &DbConnection, name: String) -> ChallengeData {
@@ -96,7 +148,9 @@ pub async fn db_get_challenge_by_name(db_connection: &DbConnection, name: String
             category: "Nope".to_string(),
             solved_by: vec![],
             running: false,
-            connection_string: "".to_string()
+            connection_string: "".to_string(),
+            start_time: 0,
+            end_time: 0
         });
     
     return chall;
@@ -112,6 +166,63 @@ pub async fn db_get_all_running_challenges(db_connection: &DbConnection) -> Vec<
     return challs;
 }
 
+// flag_rules is sealed by the flag receiver, the database never sees a plaintext flag
+pub async fn db_set_challenge_flag_rules(db_connection: &DbConnection, name: String, sealed_rules: String) -> bool {
+    let query = format!("UPDATE {table_name} SET flag_rules = $2 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
+
+    let res = sqlx::query(&query[..])
+        .bind(name.trim())
+        .bind(sealed_rules)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+// (challenge_name, running, sealed flag rules) of every challenge that was deployed at least once
+pub async fn db_get_sealed_flag_rules(db_connection: &DbConnection) -> Vec<(String, bool, String)> {
+    let query = format!("
+    SELECT challenge_name, running, flag_rules FROM {table_name}
+    WHERE flag_rules IS NOT NULL AND flag_rules <> '';", table_name=DB_CHALLENGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_upcoming_challenges(db_connection: &DbConnection, now: i64) -> Vec<ChallengeData> {
+    let query = format!("SELECT * FROM {table_name} WHERE running=false AND start_time > $1 ORDER BY start_time ASC", table_name=DB_CHALLENGE_TABLE);
+
+
+    let challs = sqlx::query_as(&query[..])
+        .bind(now)
+        .fetch_all(&db_connection.pool).await.unwrap_or_default();
+
+    challs
+}
+
+// challenges that are running or still waiting to be released, deployer and timer rebuild their state from them after a restart
+pub async fn db_get_pending_challenges(db_connection: &DbConnection, now: i64) -> Vec<ChallengeData> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY start_time ASC", table_name=DB_CHALLENGE_TABLE);
+
+    let challs: Vec<ChallengeData> = sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default();
+
+    challs.into_iter().filter(|chall| chall.is_pending(now)).collect()
+}
+
+// a destroyed challenge ends now, so a restart doesn't bring it back
+pub async fn db_end_challenge_schedule(db_connection: &DbConnection, name: String, now: i64) -> bool {
+    let query = format!("UPDATE {table_name} SET end_time = LEAST(end_time, $2) WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
+
+    let res = sqlx::query(&query[..])
+        .bind(name.trim())
+        .bind(now)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
 pub async fn db_set_challenge_running(db_connection: &DbConnection, name: String, is_running: bool) -> bool {
     let query = format!("UPDATE {table_name} SET running = $2 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
     
@@ -140,16 +251,33 @@ pub async fn db_set_challenge_connection_string(db_connection: &DbConnection, na
     return false;
 }
 
-pub async fn db_update_challenge_score(db_connection: &DbConnection, name: String, score: i32) -> bool {
-    let query = format!("UPDATE {table_name} SET score = $2 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
+// scheduled_at tells a schedule made during a pause from one the pause has to push back
+pub async fn db_set_challenge_schedule(db_connection: &DbConnection, name: String, start_time: i64, end_time: i64) -> bool {
+    let query = format!("UPDATE {table_name} SET start_time = $2, end_time = $3, scheduled_at = $4 WHERE challenge_name=$1;", table_name=DB_CHALLENGE_TABLE);
 
     let res= sqlx::query(&query[..])
         .bind(name.trim())
-        .bind(score)
-        .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
-    
-    if res.rows_affected() > 0 {
-        return true;
-    }
-    return false;
+        .bind(start_time)
+        .bind(end_time)
+        .bind(chrono::offset::Utc::now().timestamp())
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+// only schedules made before the pause started move
+pub async fn db_shift_pending_challenge_schedules(db_connection: &DbConnection, since: i64, shift: i64) -> bool {
+    let query = format!("
+    UPDATE {table_name}
+    SET
+        start_time = CASE WHEN start_time > $1 THEN start_time + $2 ELSE start_time END,
+        end_time = CASE WHEN end_time > $1 THEN end_time + $2 ELSE end_time END
+    WHERE end_time > $1 AND COALESCE(scheduled_at, 0) <= $1;", table_name=DB_CHALLENGE_TABLE);
+
+    let res = sqlx::query(&query[..])
+        .bind(since)
+        .bind(shift)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+use sqlx::postgres::{PgConnection, PgListener};
+
+use crate::database::DbConnection;
+
+pub async fn db_notify_channel(db_connection: &DbConnection, channel: &str, payload: &str) -> bool {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(channel)
+        .bind(payload)
+        .execute(&db_connection.pool).await
+        .is_ok()
+}
+
+pub async fn db_listen_channel(db_connection: &DbConnection, channel: &str) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(&db_connection.pool).await?;
+    listener.listen(channel).await?;
+
+    Ok(listener)
+}
+
+// session level lock, held for as long as the returned connection stays open
+pub async fn db_try_advisory_lock(db_connection: &DbConnection, key: i64) -> Option<PgConnection> {
+    let mut conn = db_connection.pool.acquire().await.ok()?;
+
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+        .bind(key)
+        .fetch_one(&mut *conn).await
+        .unwrap_or(false);
+
+    if locked {
+        // out of the pool, a pooled connection would carry the lock to whoever borrows it next
+        Some(conn.detach())
+    } else {
+        None
+    }
+}
+
+// whether a connection taken out of the pool still reaches the database
+pub async fn db_connection_alive(conn: &mut PgConnection) -> bool {
+    sqlx::query("SELECT 1")
+        .execute(&mut *conn).await
+        .is_ok()
+}
@@ -0,0 +1,95 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::{FromRow, Decode};
+use chrono::DateTime;
+use chrono::offset::Utc;
+
+use crate::database::{DbConnection, DbError, DB_EVENT_LOG_TABLE};
+
+#[derive(FromRow, Decode, serde::Deserialize, serde::Serialize)]
+pub struct EventLogEntry {
+    id: i32,
+    time: i64,
+    source: String,
+    topic: String,
+    payload: String
+}
+
+impl EventLogEntry {
+    pub fn new(source: String, topic: String, payload: String) -> Self {
+        EventLogEntry {
+            id: -1,
+            time: chrono::offset::Utc::now().timestamp(),
+            source,
+            topic,
+            payload
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.time, 0).unwrap_or(DateTime::from_timestamp(0, 0).unwrap())
+    }
+
+    pub fn source(&self) -> &str {
+        self.source.as_str()
+    }
+
+    pub fn topic(&self) -> &str {
+        self.topic.as_str()
+    }
+
+    pub fn payload(&self) -> &str {
+        self.payload.as_str()
+    }
+}
+
+pub async fn db_append_event_log(db_connection: &DbConnection, entry: EventLogEntry) -> Result<bool, DbError> {
+    if db_connection.is_closed() {
+        return Err(DbError::ConnectionAlreadyClosed);
+    }
+
+    let query = format!("
+    INSERT INTO {table_name} (
+        time,
+        source,
+        topic,
+        payload
+    )
+    VALUES ($1, $2, $3, $4);", table_name=DB_EVENT_LOG_TABLE);
+
+    let result: PgQueryResult = sqlx::query(&query[..])
+        .bind(entry.time)
+        .bind(entry.source())
+        .bind(entry.topic())
+        .bind(entry.payload())
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    Ok(result.rows_affected() > 0)
+}
+
+// empty source/topic match everything, `since` is an epoch in seconds
+pub async fn db_filter_event_log(db_connection: &DbConnection, source: &str, topic: &str, since: i64, limit: i32) -> Result<Vec<EventLogEntry>, DbError> {
+    if db_connection.is_closed() {
+        return Err(DbError::ConnectionAlreadyClosed);
+    }
+
+    let query = format!("
+    SELECT * FROM {table_name}
+    WHERE ($1 = '' OR source = $1)
+        AND ($2 = '' OR topic = $2)
+        AND time >= $3
+    ORDER BY time DESC, id DESC
+    LIMIT $4;", table_name=DB_EVENT_LOG_TABLE);
+
+    let records: Vec<EventLogEntry> = sqlx::query_as(&query[..])
+        .bind(source)
+        .bind(topic)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&db_connection.pool).await.unwrap_or_default();
+
+    Ok(records)
+}
@@ -0,0 +1,74 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::{FromRow, Decode};
+use chrono::DateTime;
+use chrono::offset::Utc;
+
+use crate::database::{DbConnection, DbError, DB_FLAG_REVIEW_TABLE};
+
+// someone submitted a per-user flag that belongs to another player
+#[derive(FromRow, Decode, serde::Deserialize, serde::Serialize)]
+pub struct FlagReview {
+    pub id: i32,
+    time: i64,
+    pub challenge_name: String,
+    pub submitted_by: String,
+    pub owner: String,
+    pub flag: String
+}
+
+impl FlagReview {
+    pub fn new(challenge_name: String, submitted_by: String, owner: String, flag: String) -> Self {
+        FlagReview {
+            id: -1,
+            time: chrono::offset::Utc::now().timestamp(),
+            challenge_name,
+            submitted_by,
+            owner,
+            flag
+        }
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.time, 0).unwrap_or(DateTime::from_timestamp(0, 0).unwrap())
+    }
+}
+
+pub async fn db_create_flag_review(db_connection: &DbConnection, review: FlagReview) -> Result<bool, DbError> {
+    if db_connection.is_closed() {
+        return Err(DbError::ConnectionAlreadyClosed);
+    }
+
+    let query = format!("
+    INSERT INTO {table_name} (
+        time,
+        challenge_name,
+        submitted_by,
+        owner,
+        flag
+    )
+    VALUES ($1, $2, $3, $4, $5);", table_name=DB_FLAG_REVIEW_TABLE);
+
+    let result: PgQueryResult = sqlx::query(&query[..])
+        .bind(review.time)
+        .bind(review.challenge_name)
+        .bind(review.submitted_by)
+        .bind(review.owner)
+        .bind(review.flag)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn db_get_flag_reviews(db_connection: &DbConnection, limit: i32) -> Result<Vec<FlagReview>, DbError> {
+    if db_connection.is_closed() {
+        return Err(DbError::ConnectionAlreadyClosed);
+    }
+
+    let query = format!("SELECT * FROM {table_name} ORDER BY time DESC, id DESC LIMIT $1;", table_name=DB_FLAG_REVIEW_TABLE);
+
+    let reviews: Vec<FlagReview> = sqlx::query_as(&query[..])
+        .bind(limit)
+        .fetch_all(&db_connection.pool).await.unwrap_or_default();
+
+    Ok(reviews)
+}
@@ -0,0 +1,108 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::FromRow;
+
+use crate::database::{DbConnection, DbError, DB_HINT_TABLE, DB_HINT_UNLOCK_TABLE};
+
+#[derive(FromRow, Clone, serde::Deserialize, serde::Serialize)]
+pub struct HintData {
+    #[serde(default)]
+    pub id: i32,
+    #[serde(default)]
+    pub challenge_name: String,
+    pub content: String,
+    // points taken from the player who unlocks it
+    #[serde(default)]
+    pub cost: i32,
+    // seconds after the challenge starts, 0 means right away
+    #[serde(default)]
+    pub release_after: i64
+}
+
+pub async fn db_create_hint(db_connection: &DbConnection, hint: HintData) -> bool {
+    let query = format!("
+    INSERT INTO {table_name} (challenge_name, content, cost, release_after)
+    VALUES ($1, $2, $3, $4);", table_name=DB_HINT_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(hint.challenge_name.trim())
+        .bind(hint.content)
+        .bind(hint.cost)
+        .bind(hint.release_after)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+pub async fn db_delete_hint(db_connection: &DbConnection, hint_id: i32) -> bool {
+    let query = format!("DELETE FROM {table_name} WHERE id = $1;", table_name=DB_HINT_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(hint_id)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    if res.rows_affected() > 0 {
+        let query = format!("DELETE FROM {table_name} WHERE hint_id = $1;", table_name=DB_HINT_UNLOCK_TABLE);
+        sqlx::query(&query[..])
+            .bind(hint_id)
+            .execute(&db_connection.pool).await.unwrap_or_default();
+    }
+
+    res.rows_affected() > 0
+}
+
+pub async fn db_get_hint_by_id(db_connection: &DbConnection, hint_id: i32) -> Option<HintData> {
+    let query = format!("SELECT * FROM {table_name} WHERE id = $1;", table_name=DB_HINT_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(hint_id)
+        .fetch_optional(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_all_hints(db_connection: &DbConnection) -> Vec<HintData> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY challenge_name, release_after, id;", table_name=DB_HINT_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_hints_for_challenge(db_connection: &DbConnection, challenge_name: String) -> Vec<HintData> {
+    let query = format!("SELECT * FROM {table_name} WHERE challenge_name = $1 ORDER BY release_after, id;", table_name=DB_HINT_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(challenge_name)
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+// false when the user unlocked it before
+// Ok(false) means the player had already unlocked it
+pub async fn db_unlock_hint(db_connection: &DbConnection, hint_id: i32, username: String) -> Result<bool, DbError> {
+    if db_connection.is_closed() {
+        return Err(DbError::ConnectionAlreadyClosed);
+    }
+
+    let query = format!("
+    INSERT INTO {table_name} (hint_id, username, time) VALUES ($1, $2, $3)
+    ON CONFLICT (hint_id, username) DO NOTHING;", table_name=DB_HINT_UNLOCK_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(hint_id)
+        .bind(username)
+        .bind(chrono::offset::Utc::now().timestamp())
+        .execute(&db_connection.pool).await
+        .map_err(|_| DbError::QueryFailed)?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn db_get_unlocked_hint_ids(db_connection: &DbConnection, username: String) -> Vec<i32> {
+    let query = format!("SELECT hint_id FROM {table_name} WHERE username = $1;", table_name=DB_HINT_UNLOCK_TABLE);
+
+    sqlx::query_scalar(&query[..])
+        .bind(username)
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
@@ -2,12 +2,25 @@ use challenge::ChallengeData;
 use sqlx::postgres::{PgPoolOptions, Postgres};
 use sqlx::pool::Pool;
 use std::clone::Clone;
+use crate::scoring::ScoringConfig;
 use std::vec;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub mod user;
 pub mod solve_history;
 pub mod challenge;
+pub mod event_log;
+pub mod webhook;
+pub mod cluster;
+pub mod settings;
+pub mod flag_review;
+pub mod stage;
+pub mod hint;
+pub mod solve;
+pub mod team;
+pub mod score_history;
+pub mod scoreboard;
+pub mod submit_limit;
 
 // TODO: change TEXT to VARCHAR as TEXT is slow
 // TODO: remove all the getters they are so useless 
@@ -21,11 +34,24 @@ const DB_POOL_MAX_CONNECTION: u32 = 5;
 const DB_CHALLENGE_TABLE: &str = "challenges";
 const DB_USER_TABLE: &str = "users";
 const DB_SOLVE_HISTORY_TABLE: &str = "solve_history";
+const DB_EVENT_LOG_TABLE: &str = "event_log";
+const DB_WEBHOOK_TABLE: &str = "webhooks";
+const DB_WEBHOOK_DELIVERY_TABLE: &str = "webhook_deliveries";
+const DB_SETTINGS_TABLE: &str = "settings";
+const DB_FLAG_REVIEW_TABLE: &str = "flag_reviews";
+const DB_CHALLENGE_STAGE_TABLE: &str = "challenge_stages";
+const DB_HINT_TABLE: &str = "hints";
+const DB_HINT_UNLOCK_TABLE: &str = "hint_unlocks";
+const DB_SOLVE_TABLE: &str = "solves";
+const DB_TEAM_TABLE: &str = "teams";
+const DB_TEAM_MEMBER_TABLE: &str = "team_members";
+const DB_SUBMIT_LIMIT_TABLE: &str = "submit_limits";
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum DbError {
     ConnectionAlreadyClosed,
     FetchFailed,
+    QueryFailed,
     AuthenticationFailed
 }
 
@@ -95,12 +121,18 @@ impl DbConnection {
         user.censor_password(password_censor)
     }
 
+    // nothing looks users up by id since the scoreboard is one query, kept for the user pages
+    #[allow(dead_code)]
     pub async fn get_user_by_id(&self, user_id: i32, password_censor: bool) -> user::UserInstance {
         let found_user: user::UserInstance = user::db_get_user_by_id(&self, user_id).await.unwrap();
 
         found_user.censor_password(password_censor)
     }
 
+    pub async fn get_user_by_name(&self, username: String) -> user::UserInstance {
+        user::db_get_user_by_name(self, username).await.unwrap_or(user::UserInstance::get_dead_guy_user())
+    }
+
     pub async fn filter_user(&self, filter: DbFilter<user::UserInstance>) -> Vec<user::UserInstance> {
         let users: Vec<user::UserInstance> = user::db_filter_for_user(&self, filter, 1).await.unwrap_or(
             Vec::new()
@@ -123,31 +155,103 @@ impl DbConnection {
         return result;
     }
 
+    // no caller updates a whole user anymore, it rehashes the password
+    #[allow(dead_code)]
     pub async fn edit_user(&self, user: user::UserInstance) -> bool {
         let result: bool = user::db_edit_user(self, user).await.unwrap_or(false);
 
         return result;
     }
 
-    pub async fn user_add_score(&self, username: String, challenge_name: String) -> bool {
-        let mut found_user = 
-            user::db_get_user_by_name(&self, username).await.unwrap_or(user::UserInstance::get_dead_guy_user());
+    // None when the user already solved it or the challenge isn't running, see solve::db_record_solve
+    pub async fn user_add_score(&self, username: String, challenge_name: String) -> Option<solve::SolveRecord> {
+        solve::db_record_solve(self, challenge_name, username).await.unwrap_or(None)
+    }
 
-        if found_user.id == -1 || found_user.challenge_solved.contains(&challenge_name) {
-            return false;
-        }
+    pub async fn create_team(&self, name: String, captain: String, invite_code: String) -> team::TeamCreate {
+        team::db_create_team(self, name, captain, invite_code).await.unwrap_or(team::TeamCreate::NameTaken)
+    }
 
-        let chall = challenge::db_get_challenge_by_name(&self, challenge_name.to_string()).await;
+    pub async fn join_team(&self, invite_code: String, username: String, size_limit: i64) -> team::TeamJoin {
+        team::db_join_team(self, invite_code, username, size_limit).await.unwrap_or(team::TeamJoin::NotFound)
+    }
 
-        if !chall.running {
-            return false;
-        }
+    pub async fn leave_team(&self, username: String) -> team::TeamLeave {
+        team::db_leave_team(self, username).await.unwrap_or(team::TeamLeave::NotInTeam)
+    }
+
+    pub async fn kick_team_member(&self, team: &team::TeamData, username: String) -> team::TeamLeave {
+        team::db_kick_team_member(self, team, username).await.unwrap_or(team::TeamLeave::NotInTeam)
+    }
+
+    pub async fn set_team_invite_code(&self, team_id: i32, invite_code: String) -> bool {
+        team::db_set_team_invite_code(self, team_id, invite_code).await
+    }
+
+    pub async fn delete_team(&self, team_id: i32) -> bool {
+        team::db_delete_team(self, team_id).await.unwrap_or(false)
+    }
+
+    pub async fn get_team_of(&self, username: String) -> Option<team::TeamData> {
+        team::db_get_team_of(self, username).await
+    }
+
+    pub async fn get_flag_identities(&self) -> Vec<String> {
+        team::db_get_flag_identities(self).await
+    }
+
+    pub async fn get_all_teams(&self) -> Vec<team::TeamData> {
+        team::db_get_all_teams(self).await
+    }
+
+    pub async fn get_all_team_members(&self) -> Vec<team::TeamMember> {
+        team::db_get_all_team_members(self).await
+    }
+
+    pub async fn get_teammates(&self, username: String) -> Vec<String> {
+        team::db_get_teammates(self, username).await
+    }
+
+    pub async fn get_bloods(&self) -> Vec<solve::Blood> {
+        solve::db_get_bloods(self).await
+    }
+
+    pub async fn get_scoreboard_rows(&self) -> Vec<scoreboard::ScoreboardRow> {
+        scoreboard::db_get_scoreboard_rows(self).await
+    }
+
+    pub async fn get_solve_events(&self) -> Vec<score_history::SolveEvent> {
+        score_history::db_get_solve_events(self).await
+    }
+
+    pub async fn get_stage_events(&self) -> Vec<score_history::StageEvent> {
+        score_history::db_get_stage_events(self).await
+    }
+
+    pub async fn get_hint_events(&self) -> Vec<score_history::HintEvent> {
+        score_history::db_get_hint_events(self).await
+    }
 
-        found_user.last_submission = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs() as i64;
+    // a limit that can't be checked lets the submission through, a broken database shouldn't stop the event
+    pub async fn check_submit_limits(&self, limits: &[(String, i64)], window: i64, now: i64) -> Option<u64> {
+        submit_limit::db_check_submit_limits(self, limits, window, now).await.unwrap_or_else(|err| {
+            println!("failed checking submission limits: {}", err);
+            None
+        })
+    }
 
-        challenge::db_challenge_solve(&self, challenge_name.to_string(), found_user.username.clone()).await;
-        found_user.challenge_solved.push(chall.challenge_name);
-        self.edit_user(found_user).await
+    // wrong flags in a row, including this one
+    pub async fn record_wrong_flag(&self, username: String) -> i64 {
+        user::db_record_wrong_flag(self, username).await
+    }
+
+    pub async fn reset_wrong_flags(&self, username: String) {
+        user::db_reset_wrong_flags(self, username).await
+    }
+
+    // only touches the lock columns, edit_user would rehash the password
+    pub async fn save_user_lock(&self, user: &user::UserInstance) -> bool {
+        user::db_save_user_lock(self, user).await
     }
 
     pub async fn create_user(&self, user_to_create: user::UserInstance) -> bool {
@@ -201,17 +305,168 @@ impl DbConnection {
         challenge::db_set_challenge_running(&self, name, is_running).await
     }
 
+    pub async fn set_challenge_flag_rules(&self, name: String, sealed_rules: String) -> bool {
+        challenge::db_set_challenge_flag_rules(self, name, sealed_rules).await
+    }
+
+    pub async fn set_challenge_scoring(&self, name: String, scoring: Option<ScoringConfig>) -> bool {
+        challenge::db_set_challenge_scoring(self, name, scoring).await
+    }
+
+    pub async fn recompute_challenge_score(&self, name: String) -> bool {
+        challenge::db_recompute_challenge_score(self, name).await
+    }
+
+    pub async fn recompute_all_challenge_scores(&self) -> bool {
+        challenge::db_recompute_all_challenge_scores(self).await
+    }
+
+    pub async fn get_sealed_flag_rules(&self) -> Vec<(String, bool, String)> {
+        challenge::db_get_sealed_flag_rules(self).await
+    }
+
+    pub async fn set_challenge_stages(&self, challenge_name: String, stages: Vec<(String, i32)>) -> bool {
+        stage::db_set_challenge_stages(self, challenge_name, stages).await
+    }
+
+    pub async fn challenge_stage_solve(&self, challenge_name: String, stage: String, username: String) -> bool {
+        stage::db_stage_solve(self, challenge_name, stage, username).await.unwrap_or(false)
+    }
+
+    pub async fn get_challenge_stages(&self, challenge_name: String) -> Vec<stage::ChallengeStage> {
+        stage::db_get_challenge_stages(self, challenge_name).await
+    }
+
+    pub async fn get_all_challenge_stages(&self) -> Vec<stage::ChallengeStage> {
+        stage::db_get_all_challenge_stages(self).await
+    }
+
+    pub async fn create_hint(&self, hint: hint::HintData) -> bool {
+        hint::db_create_hint(self, hint).await
+    }
+
+    pub async fn delete_hint(&self, hint_id: i32) -> bool {
+        hint::db_delete_hint(self, hint_id).await
+    }
+
+    pub async fn get_hint_by_id(&self, hint_id: i32) -> Option<hint::HintData> {
+        hint::db_get_hint_by_id(self, hint_id).await
+    }
+
+    pub async fn get_all_hints(&self) -> Vec<hint::HintData> {
+        hint::db_get_all_hints(self).await
+    }
+
+    pub async fn get_hints_for_challenge(&self, challenge_name: String) -> Vec<hint::HintData> {
+        hint::db_get_hints_for_challenge(self, challenge_name).await
+    }
+
+    pub async fn unlock_hint(&self, hint_id: i32, username: String) -> Result<bool, DbError> {
+        hint::db_unlock_hint(self, hint_id, username).await
+    }
+
+    pub async fn get_unlocked_hint_ids(&self, username: String) -> Vec<i32> {
+        hint::db_get_unlocked_hint_ids(self, username).await
+    }
+
     pub async fn set_challenge_connection_string(&self, name: String, connection_string: String) -> bool {
         challenge::db_set_challenge_connection_string(&self, name, connection_string).await
     }
 
+    pub async fn set_challenge_schedule(&self, name: String, start_time: i64, end_time: i64) -> bool {
+        challenge::db_set_challenge_schedule(self, name, start_time, end_time).await
+    }
+
+    pub async fn shift_pending_challenge_schedules(&self, since: i64, shift: i64) -> bool {
+        challenge::db_shift_pending_challenge_schedules(self, since, shift).await
+    }
+
     pub async fn get_all_running_challenges(&self) -> Vec<ChallengeData> {
         challenge::db_get_all_running_challenges(&self).await
     }
 
+    pub async fn get_upcoming_challenges(&self) -> Vec<ChallengeData> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs() as i64;
+        challenge::db_get_upcoming_challenges(self, now).await
+    }
+
+    pub async fn get_pending_challenges(&self) -> Vec<ChallengeData> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs() as i64;
+        challenge::db_get_pending_challenges(self, now).await
+    }
+
+    pub async fn end_challenge_schedule(&self, name: String) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs() as i64;
+        challenge::db_end_challenge_schedule(self, name, now).await
+    }
+
     pub async fn get_challenge_by_name(&self, name: String) -> ChallengeData {
         challenge::db_get_challenge_by_name(&self, name).await
     }
+
+    pub async fn append_event_log(&self, entry: event_log::EventLogEntry) -> bool {
+        event_log::db_append_event_log(self, entry).await.unwrap_or(false)
+    }
+
+    pub async fn filter_event_log(&self, source: &str, topic: &str, since: i64, limit: i32) -> Vec<event_log::EventLogEntry> {
+        event_log::db_filter_event_log(self, source, topic, since, limit).await.unwrap_or_default()
+    }
+
+    pub async fn create_webhook(&self, webhook: webhook::WebhookData) -> bool {
+        webhook::db_create_webhook(self, webhook).await
+    }
+
+    pub async fn delete_webhook(&self, webhook_id: i32) -> bool {
+        webhook::db_delete_webhook(self, webhook_id).await
+    }
+
+    pub async fn get_all_webhooks(&self) -> Vec<webhook::WebhookData> {
+        webhook::db_get_all_webhooks(self).await
+    }
+
+    pub async fn get_webhook_by_id(&self, webhook_id: i32) -> Option<webhook::WebhookData> {
+        webhook::db_get_webhook_by_id(self, webhook_id).await
+    }
+
+    pub async fn get_webhooks_for_topic(&self, topic: &str) -> Vec<webhook::WebhookData> {
+        webhook::db_get_webhooks_for_topic(self, topic).await
+    }
+
+    pub async fn log_webhook_delivery(&self, delivery: webhook::WebhookDelivery) -> bool {
+        webhook::db_log_webhook_delivery(self, delivery).await
+    }
+
+    pub async fn create_flag_review(&self, review: flag_review::FlagReview) -> bool {
+        flag_review::db_create_flag_review(self, review).await.unwrap_or(false)
+    }
+
+    pub async fn get_flag_reviews(&self, limit: i32) -> Vec<flag_review::FlagReview> {
+        flag_review::db_get_flag_reviews(self, limit).await.unwrap_or_default()
+    }
+
+    pub async fn get_settings(&self) -> settings::Settings {
+        settings::db_get_settings(self).await
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> bool {
+        settings::db_set_setting(self, key, value).await
+    }
+
+    pub async fn notify_channel(&self, channel: &str, payload: &str) -> bool {
+        cluster::db_notify_channel(self, channel, payload).await
+    }
+
+    pub async fn listen_channel(&self, channel: &str) -> Result<sqlx::postgres::PgListener, sqlx::Error> {
+        cluster::db_listen_channel(self, channel).await
+    }
+
+    pub async fn try_advisory_lock(&self, key: i64) -> Option<sqlx::postgres::PgConnection> {
+        cluster::db_try_advisory_lock(self, key).await
+    }
+
+    pub async fn get_recent_webhook_deliveries(&self, limit: i32) -> Vec<webhook::WebhookDelivery> {
+        webhook::db_get_recent_webhook_deliveries(self, limit).await
+    }
 }
 
 pub async fn new_db_connection() -> Result<DbConnection, sqlx::Error> {
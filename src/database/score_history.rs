@@ -0,0 +1,74 @@
+use sqlx::FromRow;
+
+use crate::database::{DbConnection, DB_CHALLENGE_STAGE_TABLE, DB_CHALLENGE_TABLE, DB_HINT_TABLE, DB_HINT_UNLOCK_TABLE, DB_SOLVE_HISTORY_TABLE, DB_SOLVE_TABLE};
+
+// a challenge solved in full, worth what the challenge is worth now plus the blood bonus
+#[derive(FromRow)]
+pub struct SolveEvent {
+    pub username: String,
+    pub challenge_name: String,
+    pub time: i64,
+    pub points: i32,
+    pub bonus: i32
+}
+
+// the first time the player solved a stage, worth weight out of total_weight of the challenge
+#[derive(FromRow)]
+pub struct StageEvent {
+    pub username: String,
+    pub challenge_name: String,
+    pub time: i64,
+    pub points: i32,
+    pub weight: i32,
+    pub total_weight: i64
+}
+
+#[derive(FromRow)]
+pub struct HintEvent {
+    pub username: String,
+    pub time: i64,
+    pub cost: i32
+}
+
+// only running challenges count, like they do on the scoreboard
+pub async fn db_get_solve_events(db_connection: &DbConnection) -> Vec<SolveEvent> {
+    let query = format!("
+    SELECT solves.username, solves.challenge_name, solves.time, challenges.score AS points, solves.bonus FROM {solve_table} solves
+    JOIN {challenge_table} challenges ON challenges.challenge_name = solves.challenge_name
+    WHERE challenges.running = true
+    ORDER BY solves.time;", solve_table=DB_SOLVE_TABLE, challenge_table=DB_CHALLENGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+// a stage counts for the player who is in its solved_by, resubmitting the flag later doesn't move it
+pub async fn db_get_stage_events(db_connection: &DbConnection) -> Vec<StageEvent> {
+    let query = format!("
+    SELECT history.username, history.challenge_name, MIN(history.time) AS time, challenges.score AS points, stages.weight,
+        (SELECT SUM(others.weight) FROM {stage_table} others WHERE others.challenge_name = history.challenge_name)::BIGINT AS total_weight
+    FROM {history_table} history
+    JOIN {stage_table} stages ON stages.challenge_name = history.challenge_name AND stages.name = history.stage
+    JOIN {challenge_table} challenges ON challenges.challenge_name = history.challenge_name
+    WHERE history.is_success = true AND history.stage <> '' AND history.username = ANY(stages.solved_by) AND challenges.running = true
+    GROUP BY history.username, history.challenge_name, history.stage, challenges.score, stages.weight
+    ORDER BY time;", history_table=DB_SOLVE_HISTORY_TABLE, stage_table=DB_CHALLENGE_STAGE_TABLE, challenge_table=DB_CHALLENGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_hint_events(db_connection: &DbConnection) -> Vec<HintEvent> {
+    let query = format!("
+    SELECT unlocks.username, unlocks.time, hints.cost FROM {unlock_table} unlocks
+    JOIN {hint_table} hints ON hints.id = unlocks.hint_id
+    JOIN {challenge_table} challenges ON challenges.challenge_name = hints.challenge_name
+    WHERE challenges.running = true AND hints.cost > 0
+    ORDER BY unlocks.time;", unlock_table=DB_HINT_UNLOCK_TABLE, hint_table=DB_HINT_TABLE, challenge_table=DB_CHALLENGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
@@ -0,0 +1,94 @@
+use sqlx::FromRow;
+
+use crate::database::{DbConnection, DB_CHALLENGE_STAGE_TABLE, DB_CHALLENGE_TABLE, DB_HINT_TABLE, DB_HINT_UNLOCK_TABLE, DB_SOLVE_TABLE, DB_TEAM_MEMBER_TABLE, DB_TEAM_TABLE, DB_USER_TABLE};
+use crate::scoring::BLOOD_BADGES;
+
+// everything the scoreboard needs about one player, only running challenges count
+#[derive(FromRow)]
+pub struct ScoreboardRow {
+    pub username: String,
+    pub last_submission: i64,
+    pub solved_score: i64,
+    // share of the stages of challenges the player's team hasn't fully solved
+    pub partial_score: i64,
+    pub bonus: i64,
+    pub hint_penalty: i64,
+    // "challenge solved/total" of those challenges
+    pub progress: Vec<String>,
+    // where the player placed on every challenge they got a blood badge on
+    pub blood_positions: Vec<i32>,
+    pub team_id: Option<i32>,
+    pub team_name: Option<String>
+}
+
+// the whole scoreboard in one round trip, every user with the sums of their solves, stages, bonuses and hints
+pub async fn db_get_scoreboard_rows(db_connection: &DbConnection) -> Vec<ScoreboardRow> {
+    let query = format!("
+    WITH solved AS (
+        SELECT users.username, challenges.challenge_name, challenges.score FROM {user_table} users
+        JOIN {challenge_table} challenges ON challenges.challenge_name = ANY(users.challenge_solved)
+        WHERE challenges.running = true
+    ),
+    team_solved AS (
+        SELECT users.username, solved.challenge_name FROM {user_table} users
+        LEFT JOIN {member_table} me ON me.username = users.username
+        LEFT JOIN {member_table} mates ON mates.team_id = me.team_id
+        JOIN solved ON solved.username = COALESCE(mates.username, users.username)
+    ),
+    stage_totals AS (
+        SELECT challenge_name, SUM(weight) AS total_weight, COUNT(*) AS stage_count FROM {stage_table}
+        GROUP BY challenge_name
+    ),
+    partial AS (
+        SELECT users.username, stages.challenge_name,
+            challenges.score * SUM(stages.weight) / GREATEST(MAX(stage_totals.total_weight), 1) AS points,
+            stages.challenge_name || ' ' || COUNT(*) || '/' || MAX(stage_totals.stage_count) AS progress
+        FROM {user_table} users
+        JOIN {stage_table} stages ON users.username = ANY(stages.solved_by)
+        JOIN {challenge_table} challenges ON challenges.challenge_name = stages.challenge_name
+        JOIN stage_totals ON stage_totals.challenge_name = stages.challenge_name
+        WHERE challenges.running = true AND NOT EXISTS (
+            SELECT 1 FROM team_solved WHERE team_solved.username = users.username AND team_solved.challenge_name = stages.challenge_name
+        )
+        GROUP BY users.username, stages.challenge_name, challenges.score
+    )
+    SELECT users.username, COALESCE(users.last_submission, 0) AS last_submission,
+        COALESCE((SELECT SUM(solved.score) FROM solved WHERE solved.username = users.username), 0)::BIGINT AS solved_score,
+        COALESCE((SELECT SUM(partial.points) FROM partial WHERE partial.username = users.username), 0)::BIGINT AS partial_score,
+        COALESCE((
+            SELECT SUM(solves.bonus) FROM {solve_table} solves
+            JOIN {challenge_table} challenges ON challenges.challenge_name = solves.challenge_name
+            WHERE solves.username = users.username AND challenges.running = true
+        ), 0)::BIGINT AS bonus,
+        COALESCE((
+            SELECT SUM(hints.cost) FROM {unlock_table} unlocks
+            JOIN {hint_table} hints ON hints.id = unlocks.hint_id
+            JOIN {challenge_table} challenges ON challenges.challenge_name = hints.challenge_name
+            WHERE unlocks.username = users.username AND challenges.running = true
+        ), 0)::BIGINT AS hint_penalty,
+        ARRAY(SELECT partial.progress FROM partial WHERE partial.username = users.username ORDER BY partial.challenge_name) AS progress,
+        ARRAY(
+            SELECT solves.position FROM {solve_table} solves
+            JOIN {challenge_table} challenges ON challenges.challenge_name = solves.challenge_name
+            WHERE solves.username = users.username AND challenges.running = true AND solves.position BETWEEN 1 AND $1
+            ORDER BY solves.challenge_name
+        ) AS blood_positions,
+        teams.id AS team_id, teams.name AS team_name
+    FROM {user_table} users
+    LEFT JOIN {member_table} members ON members.username = users.username
+    LEFT JOIN {team_table} teams ON teams.id = members.team_id;",
+        user_table=DB_USER_TABLE, challenge_table=DB_CHALLENGE_TABLE, stage_table=DB_CHALLENGE_STAGE_TABLE, solve_table=DB_SOLVE_TABLE,
+        unlock_table=DB_HINT_UNLOCK_TABLE, hint_table=DB_HINT_TABLE, member_table=DB_TEAM_MEMBER_TABLE, team_table=DB_TEAM_TABLE);
+
+    let rows = sqlx::query_as(&query[..])
+        .bind(BLOOD_BADGES.len() as i32)
+        .fetch_all(&db_connection.pool).await;
+
+    match rows {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("failed computing the scoreboard: {}", err);
+            vec![]
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use sqlx::postgres::{PgConnection, PgQueryResult};
+
+use crate::database::{DbConnection, DB_SETTINGS_TABLE};
+use crate::scoring::{BloodBonus, ScoringConfig};
+
+// epoch after which submissions are refused, 0 means the event never ends
+pub const EVENT_END_SETTING: &str = "event_end";
+pub const SUBMIT_WINDOW_SETTING: &str = "submit_window";
+pub const SUBMIT_USER_LIMIT_SETTING: &str = "submit_user_limit";
+pub const SUBMIT_IP_LIMIT_SETTING: &str = "submit_ip_limit";
+pub const CHALLENGE_COOLDOWN_SETTING: &str = "challenge_cooldown";
+pub const LOCKOUT_THRESHOLD_SETTING: &str = "lockout_threshold";
+pub const LOCKOUT_DURATION_SETTING: &str = "lockout_duration";
+// 1 ranks teams on the scoreboard instead of players
+pub const TEAM_EVENT_SETTING: &str = "team_event";
+pub const TEAM_SIZE_SETTING: &str = "team_size";
+// JSON ScoringConfig of every challenge without its own, not one of KNOWN_SETTINGS as it isn't an integer
+pub const SCORING_SETTING: &str = "scoring";
+// JSON BloodBonus, same as scoring
+pub const BLOOD_BONUS_SETTING: &str = "blood_bonus";
+// epoch the event was paused at, 0 while it runs. set by the timer so a restart or a new leader stays paused
+pub const PAUSED_AT_SETTING: &str = "paused_at";
+
+pub struct SettingInfo {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub default: i64
+}
+
+// every setting admins can change from sheep center, all of them are integers
+pub const KNOWN_SETTINGS: [SettingInfo; 9] = [
+    SettingInfo { key: EVENT_END_SETTING, label: "Submissions close at", default: 0 },
+    SettingInfo { key: SUBMIT_WINDOW_SETTING, label: "Rate limit window (seconds)", default: 60 },
+    SettingInfo { key: SUBMIT_USER_LIMIT_SETTING, label: "Submissions per user per window (0 disables)", default: 10 },
+    SettingInfo { key: SUBMIT_IP_LIMIT_SETTING, label: "Submissions per IP per window (0 disables)", default: 30 },
+    SettingInfo { key: CHALLENGE_COOLDOWN_SETTING, label: "Seconds between guesses on the same challenge", default: 5 },
+    SettingInfo { key: LOCKOUT_THRESHOLD_SETTING, label: "Wrong flags in a row before the account is locked (0 disables)", default: 30 },
+    SettingInfo { key: LOCKOUT_DURATION_SETTING, label: "Account lock duration (seconds)", default: 900 },
+    SettingInfo { key: TEAM_EVENT_SETTING, label: "Team event, 1 ranks teams on the scoreboard", default: 0 },
+    SettingInfo { key: TEAM_SIZE_SETTING, label: "Members per team (0 is unlimited)", default: 4 }
+];
+
+pub fn is_known_setting(key: &str) -> bool {
+    KNOWN_SETTINGS.iter().any(|setting| setting.key == key)
+}
+
+// stored settings, unset or unparsable ones fall back to their default
+pub struct Settings {
+    values: HashMap<String, String>
+}
+
+impl Settings {
+    pub fn get(&self, key: &str) -> i64 {
+        let default = KNOWN_SETTINGS.iter()
+            .find(|setting| setting.key == key)
+            .map(|setting| setting.default)
+            .unwrap_or_default();
+
+        self.values.get(key)
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(default)
+    }
+
+    pub fn scoring(&self) -> ScoringConfig {
+        self.values.get(SCORING_SETTING)
+            .and_then(|value| serde_json::from_str(value).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn blood_bonus(&self) -> BloodBonus {
+        self.values.get(BLOOD_BONUS_SETTING)
+            .and_then(|value| serde_json::from_str(value).ok())
+            .unwrap_or_default()
+    }
+}
+
+pub async fn db_get_settings(db_connection: &DbConnection) -> Settings {
+    let query = format!("SELECT key, value FROM {table_name}", table_name=DB_SETTINGS_TABLE);
+
+    let rows: Vec<(String, String)> = sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default();
+
+    Settings { values: rows.into_iter().collect() }
+}
+
+pub async fn db_set_setting(db_connection: &DbConnection, key: &str, value: &str) -> bool {
+    let query = format!("
+    INSERT INTO {table_name} (key, value) VALUES ($1, $2)
+    ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value;", table_name=DB_SETTINGS_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(key)
+        .bind(value)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+// one JSON setting, read inside a caller's transaction
+pub async fn get_json_setting<T: serde::de::DeserializeOwned + Default>(conn: &mut PgConnection, key: &str) -> Result<T, sqlx::Error> {
+    let query = format!("SELECT value FROM {table_name} WHERE key = $1;", table_name=DB_SETTINGS_TABLE);
+
+    let value: Option<String> = sqlx::query_scalar(&query[..])
+        .bind(key)
+        .fetch_optional(&mut *conn).await?;
+
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()).unwrap_or_default())
+}
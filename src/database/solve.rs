@@ -0,0 +1,116 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::FromRow;
+
+use crate::database::{challenge::update_challenge_score, settings::{get_json_setting, BLOOD_BONUS_SETTING}, user::lock_user, DbConnection, DB_CHALLENGE_TABLE, DB_SOLVE_TABLE, DB_TEAM_MEMBER_TABLE, DB_USER_TABLE};
+use crate::scoring::{BloodBonus, BLOOD_BADGES};
+
+// where the solver placed on the challenge, 1 is the first blood
+pub struct SolveRecord {
+    pub position: i32,
+    pub bonus: i32
+}
+
+#[derive(FromRow)]
+pub struct Blood {
+    pub challenge_name: String,
+    pub username: String,
+    pub position: i32
+}
+
+// records a solve exactly once: the solves row with its blood bonus, the challenge's solved_by and score
+// and the user's challenge_solved all change in one transaction, or none of them do.
+// None when the user or their team already solved the challenge or it isn't running.
+pub async fn db_record_solve(db_connection: &DbConnection, challenge_name: String, username: String) -> Result<Option<SolveRecord>, sqlx::Error> {
+    let now = chrono::offset::Utc::now().timestamp();
+    let mut tx = db_connection.pool.begin().await?;
+
+    // the player can't join or leave a team until the solve is in
+    lock_user(&mut tx, &username).await?;
+
+    let query = format!("SELECT team_id FROM {table_name} WHERE username = $1;", table_name=DB_TEAM_MEMBER_TABLE);
+    let team_id: Option<i32> = sqlx::query_scalar(&query[..])
+        .bind(&username)
+        .fetch_optional(&mut *tx).await?;
+
+    // the unique (challenge_name, username) and (challenge_name, team_id) make concurrent submissions
+    // of the same player or team wait on each other, a team solves every challenge once
+    let query = format!("
+    INSERT INTO {table_name} (challenge_name, username, time, position, bonus, team_id) VALUES ($1, $2, $3, 0, 0, $4)
+    ON CONFLICT DO NOTHING;", table_name=DB_SOLVE_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(&challenge_name)
+        .bind(&username)
+        .bind(now)
+        .bind(team_id.unwrap_or_default())
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    // locks the challenge row, solves of other players are applied one after another so none is lost
+    let query = format!("
+    UPDATE {table_name} SET solved_by = array_append(solved_by, $2)
+    WHERE challenge_name = $1 AND running = true AND NOT ($2 = ANY(solved_by))
+    RETURNING cardinality(solved_by);", table_name=DB_CHALLENGE_TABLE);
+
+    let position: Option<i32> = sqlx::query_scalar(&query[..])
+        .bind(&challenge_name)
+        .bind(&username)
+        .fetch_optional(&mut *tx).await?;
+
+    let position = match position {
+        Some(position) => position,
+        None => {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+    };
+
+    let points = update_challenge_score(&mut tx, &challenge_name).await?.unwrap_or_default();
+    let blood_bonus: BloodBonus = get_json_setting(&mut tx, BLOOD_BONUS_SETTING).await?;
+    let bonus = blood_bonus.bonus(position, points);
+
+    let query = format!("UPDATE {table_name} SET position = $3, bonus = $4 WHERE challenge_name = $1 AND username = $2;", table_name=DB_SOLVE_TABLE);
+    sqlx::query(&query[..])
+        .bind(&challenge_name)
+        .bind(&username)
+        .bind(position)
+        .bind(bonus)
+        .execute(&mut *tx).await?;
+
+    // only the solve columns, rewriting the whole row would rehash the password
+    let query = format!("
+    UPDATE {table_name} SET challenge_solved = array_append(challenge_solved, $1), last_submission = $3
+    WHERE username = $2;", table_name=DB_USER_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(&challenge_name)
+        .bind(&username)
+        .bind(now)
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+    Ok(Some(SolveRecord { position, bonus }))
+}
+
+// solvers that earned a badge on a running challenge, in order
+pub async fn db_get_bloods(db_connection: &DbConnection) -> Vec<Blood> {
+    let query = format!("
+    SELECT solves.challenge_name, solves.username, solves.position FROM {solve_table} solves
+    JOIN {challenge_table} challenges ON challenges.challenge_name = solves.challenge_name
+    WHERE challenges.running = true AND solves.position BETWEEN 1 AND $1
+    ORDER BY solves.challenge_name, solves.position;", solve_table=DB_SOLVE_TABLE, challenge_table=DB_CHALLENGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(BLOOD_BADGES.len() as i32)
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
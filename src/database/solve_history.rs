@@ -12,7 +12,9 @@ pub struct SolveHistoryEntry {
     username: String,
     is_success: bool,
     time: i64,
-    submit_content: String
+    submit_content: String,
+    // empty unless the flag solved a stage of a multi-stage challenge
+    stage: String
 }
 
 impl SolveHistoryEntry {
@@ -23,9 +25,15 @@ impl SolveHistoryEntry {
             username,
             is_success,
             submit_content,
-            time: chrono::offset::Utc::now().timestamp()
+            time: chrono::offset::Utc::now().timestamp(),
+            stage: String::new()
         }
     }
+
+    pub fn with_stage(mut self, stage: String) -> Self {
+        self.stage = stage;
+        self
+    }
     pub fn id(&self) -> i32 {
         self.id
     }
@@ -55,6 +63,10 @@ impl SolveHistoryEntry {
         &self.challenge_name
     }
 
+    pub fn stage(&self) -> &str {
+        &self.stage
+    }
+
     pub fn get_empty_solve_history_entry() -> Self {
         SolveHistoryEntry {
             id: -1,
@@ -62,7 +74,8 @@ impl SolveHistoryEntry {
             challenge_name: String::from("nothing"),
             is_success: false,
             time: -1,
-            submit_content: "".to_string()
+            submit_content: "".to_string(),
+            stage: "".to_string()
         }
     }
 }
@@ -78,7 +91,8 @@ pub async fn db_save_solve_result(db_connection: &DbConnection, solve_entry: Sol
         challenge_name,
         is_success,
         time,
-        submit_content
+        submit_content,
+        stage
     )
     VALUES
         (
@@ -86,7 +100,8 @@ pub async fn db_save_solve_result(db_connection: &DbConnection, solve_entry: Sol
             $2,
             $3,
             $4,
-            $5
+            $5,
+            $6
         );", table_name=DB_SOLVE_HISTORY_TABLE);
 
         let result: PgQueryResult = sqlx::query(&query[..])
@@ -95,6 +110,7 @@ pub async fn db_save_solve_result(db_connection: &DbConnection, solve_entry: Sol
         .bind(solve_entry.is_success())
         .bind(solve_entry.raw_time())
         .bind(solve_entry.submit_content())
+        .bind(solve_entry.stage())
         .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
 
     if result.rows_affected() > 0 {
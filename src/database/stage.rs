@@ -0,0 +1,85 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::FromRow;
+
+use crate::database::{user::lock_user, DbConnection, DB_CHALLENGE_STAGE_TABLE, DB_TEAM_MEMBER_TABLE};
+
+// one step of a multi-stage challenge, solving it is worth weight / total weight of the challenge
+#[derive(FromRow, Clone)]
+pub struct ChallengeStage {
+    #[allow(dead_code)]
+    pub id: i32,
+    pub challenge_name: String,
+    pub name: String,
+    pub weight: i32,
+    pub solved_by: Vec<String>
+}
+
+// replaces the stages of a challenge, solves of stages that still exist are kept
+pub async fn db_set_challenge_stages(db_connection: &DbConnection, challenge_name: String, stages: Vec<(String, i32)>) -> bool {
+    let names: Vec<String> = stages.iter().map(|(name, _)| name.clone()).collect();
+
+    let query = format!("DELETE FROM {table_name} WHERE challenge_name = $1 AND NOT (name = ANY($2));", table_name=DB_CHALLENGE_STAGE_TABLE);
+    sqlx::query(&query[..])
+        .bind(&challenge_name)
+        .bind(&names)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    let no_one_solved: Vec<String> = vec![];
+    let query = format!("
+    INSERT INTO {table_name} (challenge_name, name, weight, solved_by) VALUES ($1, $2, $3, $4)
+    ON CONFLICT (challenge_name, name) DO UPDATE SET weight = EXCLUDED.weight;", table_name=DB_CHALLENGE_STAGE_TABLE);
+
+    let mut success = true;
+    for (name, weight) in stages {
+        let res: PgQueryResult = sqlx::query(&query[..])
+            .bind(&challenge_name)
+            .bind(name)
+            .bind(weight)
+            .bind(&no_one_solved)
+            .execute(&db_connection.pool).await.unwrap_or_default();
+        success &= res.rows_affected() > 0;
+    }
+    success
+}
+
+// false when the user or one of their teammates already solved the stage.
+// the teammates are read after locking the user, so the player can't switch teams in between
+pub async fn db_stage_solve(db_connection: &DbConnection, challenge_name: String, stage: String, username: String) -> Result<bool, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    lock_user(&mut tx, &username).await?;
+
+    let query = format!("
+    UPDATE {table_name} SET solved_by = array_append(solved_by, $3)
+    WHERE challenge_name = $1 AND name = $2 AND NOT ($3 = ANY(solved_by)) AND NOT (solved_by && ARRAY(
+        SELECT mates.username FROM {member_table} me
+        JOIN {member_table} mates ON mates.team_id = me.team_id
+        WHERE me.username = $3
+    ));", table_name=DB_CHALLENGE_STAGE_TABLE, member_table=DB_TEAM_MEMBER_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(challenge_name)
+        .bind(stage)
+        .bind(&username)
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn db_get_challenge_stages(db_connection: &DbConnection, challenge_name: String) -> Vec<ChallengeStage> {
+    let query = format!("SELECT * FROM {table_name} WHERE challenge_name = $1 ORDER BY id;", table_name=DB_CHALLENGE_STAGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(challenge_name)
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_all_challenge_stages(db_connection: &DbConnection) -> Vec<ChallengeStage> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY id;", table_name=DB_CHALLENGE_STAGE_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
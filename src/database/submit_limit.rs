@@ -0,0 +1,44 @@
+use crate::database::{DbConnection, DB_SUBMIT_LIMIT_TABLE};
+use crate::flag_receiver::rate_limit::limit_hit;
+
+// (key, limit) pairs like ("user:alice", 10) share one window, the submission counts for all of them or none.
+// the rows stay locked until the submission is recorded, so processes checking the same key take turns
+pub async fn db_check_submit_limits(db_connection: &DbConnection, limits: &[(String, i64)], window: i64, now: i64) -> Result<Option<u64>, sqlx::Error> {
+    let keys: Vec<String> = limits.iter().map(|(key, _)| key.clone()).collect();
+    let mut tx = db_connection.pool.begin().await?;
+
+    let query = format!("INSERT INTO {table_name} (key, hits) SELECT unnest($1::TEXT[]), '{{}}' ON CONFLICT DO NOTHING;", table_name=DB_SUBMIT_LIMIT_TABLE);
+    sqlx::query(&query[..])
+        .bind(&keys)
+        .execute(&mut *tx).await?;
+
+    // ordered by key so two submissions never lock the same rows the other way around
+    let query = format!("
+    SELECT key, ARRAY(SELECT hit FROM unnest(hits) hit WHERE hit > $2 ORDER BY hit) FROM {table_name}
+    WHERE key = ANY($1) ORDER BY key FOR UPDATE;", table_name=DB_SUBMIT_LIMIT_TABLE);
+
+    let windows: Vec<(String, Vec<i64>)> = sqlx::query_as(&query[..])
+        .bind(&keys)
+        .bind(now - window)
+        .fetch_all(&mut *tx).await?;
+
+    for (key, limit) in limits {
+        let hits = windows.iter().find(|(other, _)| other == key).map(|(_, hits)| &hits[..]).unwrap_or_default();
+        if let Some(wait) = limit_hit(hits, *limit, window, now) {
+            return Ok(Some(wait));
+        }
+    }
+
+    let query = format!("
+    UPDATE {table_name} SET hits = array_append(ARRAY(SELECT hit FROM unnest(hits) hit WHERE hit > $2 ORDER BY hit), $3)
+    WHERE key = ANY($1);", table_name=DB_SUBMIT_LIMIT_TABLE);
+
+    sqlx::query(&query[..])
+        .bind(&keys)
+        .bind(now - window)
+        .bind(now)
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(None)
+}
@@ -0,0 +1,337 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::FromRow;
+
+use sqlx::postgres::PgConnection;
+
+use crate::database::{user::lock_user, DbConnection, DB_SOLVE_TABLE, DB_TEAM_MEMBER_TABLE, DB_TEAM_TABLE, DB_USER_TABLE};
+
+#[derive(FromRow, Clone, serde::Serialize)]
+pub struct TeamData {
+    pub id: i32,
+    pub name: String,
+    // players join with it, only members get to see it
+    pub invite_code: String,
+    pub captain: String,
+    pub created_at: i64
+}
+
+#[derive(FromRow, Clone)]
+pub struct TeamMember {
+    pub team_id: i32,
+    pub username: String,
+    #[allow(dead_code)]
+    pub joined_at: i64
+}
+
+pub enum TeamCreate {
+    Created(TeamData),
+    NameTaken,
+    AlreadyInTeam,
+    HasSolves
+}
+
+pub enum TeamJoin {
+    Joined(TeamData),
+    NotFound,
+    Full,
+    AlreadyInTeam,
+    HasSolves
+}
+
+pub enum TeamLeave {
+    Left,
+    NotInTeam,
+    HasSolves
+}
+
+// A player who scored can't bring the solves into a team or take them out of one, the team could solve
+// the same challenges again. The player's row stays locked while the team changes, see lock_user.
+
+// the name is taken when a team or a player already has it
+pub async fn db_create_team(db_connection: &DbConnection, name: String, captain: String, invite_code: String) -> Result<TeamCreate, sqlx::Error> {
+    let now = chrono::offset::Utc::now().timestamp();
+    let mut tx = db_connection.pool.begin().await?;
+
+    if lock_user(&mut tx, &captain).await? {
+        tx.rollback().await?;
+        return Ok(TeamCreate::HasSolves);
+    }
+
+    // players without a team show up on the team scoreboard under their own name
+    let query = format!("SELECT EXISTS (SELECT 1 FROM {table_name} WHERE username = $1);", table_name=DB_USER_TABLE);
+    let is_username: bool = sqlx::query_scalar(&query[..])
+        .bind(name.trim())
+        .fetch_one(&mut *tx).await?;
+
+    if is_username {
+        tx.rollback().await?;
+        return Ok(TeamCreate::NameTaken);
+    }
+
+    let query = format!("
+    INSERT INTO {table_name} (name, invite_code, captain, created_at) VALUES ($1, $2, $3, $4)
+    ON CONFLICT DO NOTHING
+    RETURNING *;", table_name=DB_TEAM_TABLE);
+
+    let team: Option<TeamData> = sqlx::query_as(&query[..])
+        .bind(name.trim())
+        .bind(invite_code)
+        .bind(&captain)
+        .bind(now)
+        .fetch_optional(&mut *tx).await?;
+
+    let team = match team {
+        Some(team) => team,
+        None => {
+            tx.rollback().await?;
+            return Ok(TeamCreate::NameTaken);
+        }
+    };
+
+    let query = format!("
+    INSERT INTO {table_name} (team_id, username, joined_at) VALUES ($1, $2, $3)
+    ON CONFLICT (username) DO NOTHING;", table_name=DB_TEAM_MEMBER_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(team.id)
+        .bind(&captain)
+        .bind(now)
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(TeamCreate::AlreadyInTeam);
+    }
+
+    tx.commit().await?;
+    Ok(TeamCreate::Created(team))
+}
+
+// size_limit 0 is unlimited, the team row is locked so two players can't both take the last spot
+pub async fn db_join_team(db_connection: &DbConnection, invite_code: String, username: String, size_limit: i64) -> Result<TeamJoin, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    if lock_user(&mut tx, &username).await? {
+        return Ok(TeamJoin::HasSolves);
+    }
+
+    let query = format!("SELECT * FROM {table_name} WHERE invite_code = $1 FOR UPDATE;", table_name=DB_TEAM_TABLE);
+    let team: Option<TeamData> = sqlx::query_as(&query[..])
+        .bind(invite_code.trim())
+        .fetch_optional(&mut *tx).await?;
+
+    let team = match team {
+        Some(team) => team,
+        None => return Ok(TeamJoin::NotFound)
+    };
+
+    let query = format!("SELECT COUNT(*) FROM {table_name} WHERE team_id = $1;", table_name=DB_TEAM_MEMBER_TABLE);
+    let member_count: i64 = sqlx::query_scalar(&query[..])
+        .bind(team.id)
+        .fetch_one(&mut *tx).await?;
+
+    if size_limit > 0 && member_count >= size_limit {
+        return Ok(TeamJoin::Full);
+    }
+
+    let query = format!("
+    INSERT INTO {table_name} (team_id, username, joined_at) VALUES ($1, $2, $3)
+    ON CONFLICT (username) DO NOTHING;", table_name=DB_TEAM_MEMBER_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(team.id)
+        .bind(username)
+        .bind(chrono::offset::Utc::now().timestamp())
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        return Ok(TeamJoin::AlreadyInTeam);
+    }
+
+    tx.commit().await?;
+    Ok(TeamJoin::Joined(team))
+}
+
+// takes the player out of their team, the oldest member left becomes captain when the captain goes
+// and the last one out deletes the team. None when the player isn't in a team
+async fn remove_member(conn: &mut PgConnection, username: &str) -> Result<Option<i32>, sqlx::Error> {
+    let query = format!("DELETE FROM {table_name} WHERE username = $1 RETURNING team_id;", table_name=DB_TEAM_MEMBER_TABLE);
+    let team_id: Option<i32> = sqlx::query_scalar(&query[..])
+        .bind(username)
+        .fetch_optional(&mut *conn).await?;
+
+    let team_id = match team_id {
+        Some(team_id) => team_id,
+        None => return Ok(None)
+    };
+
+    let query = format!("SELECT username FROM {table_name} WHERE team_id = $1 ORDER BY joined_at, username LIMIT 1;", table_name=DB_TEAM_MEMBER_TABLE);
+    let next_captain: Option<String> = sqlx::query_scalar(&query[..])
+        .bind(team_id)
+        .fetch_optional(&mut *conn).await?;
+
+    match next_captain {
+        Some(next_captain) => {
+            let query = format!("UPDATE {table_name} SET captain = $2 WHERE id = $1 AND captain = $3;", table_name=DB_TEAM_TABLE);
+            sqlx::query(&query[..])
+                .bind(team_id)
+                .bind(next_captain)
+                .bind(username)
+                .execute(&mut *conn).await?;
+        },
+        None => {
+            let query = format!("DELETE FROM {table_name} WHERE id = $1;", table_name=DB_TEAM_TABLE);
+            sqlx::query(&query[..])
+                .bind(team_id)
+                .execute(&mut *conn).await?;
+        }
+    }
+    Ok(Some(team_id))
+}
+
+pub async fn db_leave_team(db_connection: &DbConnection, username: String) -> Result<TeamLeave, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    let has_solves = lock_user(&mut tx, &username).await?;
+    if remove_member(&mut tx, &username).await?.is_none() {
+        return Ok(TeamLeave::NotInTeam);
+    }
+
+    if has_solves {
+        tx.rollback().await?;
+        return Ok(TeamLeave::HasSolves);
+    }
+
+    tx.commit().await?;
+    Ok(TeamLeave::Left)
+}
+
+// for deleted users, their solves are deleted with them so there is nothing left to keep apart
+pub async fn db_remove_team_member(db_connection: &DbConnection, username: String) -> Result<bool, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    let removed = remove_member(&mut tx, &username).await?.is_some();
+    tx.commit().await?;
+    Ok(removed)
+}
+
+// the captain can't kick themselves, they leave instead
+pub async fn db_kick_team_member(db_connection: &DbConnection, team: &TeamData, username: String) -> Result<TeamLeave, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    let has_solves = lock_user(&mut tx, &username).await?;
+
+    let query = format!("DELETE FROM {table_name} WHERE team_id = $1 AND username = $2 AND username <> $3;", table_name=DB_TEAM_MEMBER_TABLE);
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(team.id)
+        .bind(&username)
+        .bind(&team.captain)
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        return Ok(TeamLeave::NotInTeam);
+    }
+
+    if has_solves {
+        tx.rollback().await?;
+        return Ok(TeamLeave::HasSolves);
+    }
+
+    tx.commit().await?;
+    Ok(TeamLeave::Left)
+}
+
+pub async fn db_set_team_invite_code(db_connection: &DbConnection, team_id: i32, invite_code: String) -> bool {
+    let query = format!("UPDATE {table_name} SET invite_code = $2 WHERE id = $1;", table_name=DB_TEAM_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(team_id)
+        .bind(invite_code)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+// the members' solves stay theirs, without the team id they no longer block anyone
+pub async fn db_delete_team(db_connection: &DbConnection, team_id: i32) -> Result<bool, sqlx::Error> {
+    let mut tx = db_connection.pool.begin().await?;
+
+    let query = format!("DELETE FROM {table_name} WHERE id = $1;", table_name=DB_TEAM_TABLE);
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(team_id)
+        .execute(&mut *tx).await?;
+
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    let query = format!("DELETE FROM {table_name} WHERE team_id = $1;", table_name=DB_TEAM_MEMBER_TABLE);
+    sqlx::query(&query[..])
+        .bind(team_id)
+        .execute(&mut *tx).await?;
+
+    let query = format!("UPDATE {table_name} SET team_id = 0 WHERE team_id = $1;", table_name=DB_SOLVE_TABLE);
+    sqlx::query(&query[..])
+        .bind(team_id)
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn db_get_team_of(db_connection: &DbConnection, username: String) -> Option<TeamData> {
+    let query = format!("
+    SELECT teams.* FROM {team_table} teams
+    JOIN {member_table} members ON members.team_id = teams.id
+    WHERE members.username = $1;", team_table=DB_TEAM_TABLE, member_table=DB_TEAM_MEMBER_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(username)
+        .fetch_optional(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_all_teams(db_connection: &DbConnection) -> Vec<TeamData> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY id;", table_name=DB_TEAM_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+pub async fn db_get_all_team_members(db_connection: &DbConnection) -> Vec<TeamMember> {
+    let query = format!("SELECT team_id, username, joined_at FROM {table_name} ORDER BY team_id, joined_at, username;", table_name=DB_TEAM_MEMBER_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
+
+// everyone whose solves count for the player, the player alone when they have no team
+pub async fn db_get_teammates(db_connection: &DbConnection, username: String) -> Vec<String> {
+    let query = format!("
+    SELECT username FROM {table_name}
+    WHERE team_id = (SELECT team_id FROM {table_name} WHERE username = $1);", table_name=DB_TEAM_MEMBER_TABLE);
+
+    let teammates: Vec<String> = sqlx::query_scalar(&query[..])
+        .bind(&username)
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default();
+
+    if teammates.is_empty() {
+        return vec![username];
+    }
+    teammates
+}
+
+// everyone per-player flags are derived for: teams, and players without a team
+pub async fn db_get_flag_identities(db_connection: &DbConnection) -> Vec<String> {
+    let query = format!("
+    SELECT DISTINCT COALESCE(teams.name, users.username) FROM {user_table} users
+    LEFT JOIN {member_table} members ON members.username = users.username
+    LEFT JOIN {team_table} teams ON teams.id = members.team_id;", user_table=DB_USER_TABLE, member_table=DB_TEAM_MEMBER_TABLE, team_table=DB_TEAM_TABLE);
+
+    sqlx::query_scalar(&query[..])
+        .fetch_all(&db_connection.pool).await
+        .unwrap_or_default()
+}
@@ -1,18 +1,12 @@
 use sqlx::{Decode, FromRow};
-use sqlx::postgres::PgQueryResult;
+use sqlx::postgres::{PgConnection, PgQueryResult};
 use chrono::{DateTime, offset::Utc};
 use bcrypt::{verify as bcrypt_verify, hash as bcrypt_hash};
 use serde;
     
 use crate::database::{DbConnection, DbFilter, DB_USER_TABLE};
 
-use super::{DB_CHALLENGE_TABLE, DB_SOLVE_HISTORY_TABLE};
-
-const MINIMUM_SCORE: i32 = 50;
-const INITIAL_SCORE: i32 = 500;
-// lower this makes score decay faster
-const DECAY_VALUE: i32 = 300;
-const SOLVES_BEFORE_DECAY: i32 = 1;
+use super::{challenge::db_recompute_challenge_score, team::db_remove_team_member, DB_CHALLENGE_STAGE_TABLE, DB_CHALLENGE_TABLE, DB_SOLVE_HISTORY_TABLE, DB_SOLVE_TABLE};
 
 #[derive(FromRow, Decode, serde::Deserialize, serde::Serialize, Debug)]
 pub struct UserInstance {
@@ -73,9 +67,14 @@ impl UserInstance {
         self.is_locked = false;
     }
 
-    #[allow(dead_code)]
-    pub fn lock(&mut self) {
+    pub fn lock(&mut self, due_at: i64) {
         self.is_locked = true;
+        self.lock_due_at = due_at;
+    }
+
+    // a lock only holds until lock_due_at, same as on login
+    pub fn is_locked_at(&self, now: i64) -> bool {
+        self.is_locked && self.lock_due_at > now
     }
 
     // a dead guy cannot login, return when a authentication failure occure
@@ -227,6 +226,54 @@ pub async fn db_edit_user(db_connection: &DbConnection, user: UserInstance) -> R
     return Ok(false);
 }
 
+pub async fn db_save_user_lock(db_connection: &DbConnection, user: &UserInstance) -> bool {
+    let query = format!("UPDATE {table_name} SET is_locked = $1, lock_due_at = $2 WHERE id = $3", table_name=DB_USER_TABLE);
+
+    let result: PgQueryResult = sqlx::query(&query[..])
+        .bind(user.is_locked)
+        .bind(user.lock_due_at)
+        .bind(user.id)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    result.rows_affected() > 0
+}
+
+pub async fn db_record_wrong_flag(db_connection: &DbConnection, username: String) -> i64 {
+    let query = format!("UPDATE {table_name} SET wrong_streak = COALESCE(wrong_streak, 0) + 1 WHERE username = $1 RETURNING wrong_streak;", table_name=DB_USER_TABLE);
+
+    sqlx::query_scalar::<_, i32>(&query[..])
+        .bind(username)
+        .fetch_optional(&db_connection.pool).await
+        .unwrap_or_default()
+        .map(i64::from)
+        .unwrap_or_default()
+}
+
+pub async fn db_reset_wrong_flags(db_connection: &DbConnection, username: String) {
+    let query = format!("UPDATE {table_name} SET wrong_streak = 0 WHERE username = $1 AND wrong_streak <> 0;", table_name=DB_USER_TABLE);
+
+    sqlx::query(&query[..])
+        .bind(username)
+        .execute(&db_connection.pool).await
+        .unwrap_or_default();
+}
+
+// locks the user's row until the caller's transaction ends, so solves and team changes of one player take turns.
+// true when the player already scored, a challenge or a stage of one
+pub async fn lock_user(conn: &mut PgConnection, username: &str) -> Result<bool, sqlx::Error> {
+    let query = format!("
+    SELECT COALESCE(cardinality(challenge_solved), 0) > 0 OR EXISTS (
+        SELECT 1 FROM {stage_table} stages WHERE users.username = ANY(stages.solved_by)
+    ) FROM {user_table} users
+    WHERE username = $1 FOR UPDATE;", user_table=DB_USER_TABLE, stage_table=DB_CHALLENGE_STAGE_TABLE);
+
+    let has_solves: Option<bool> = sqlx::query_scalar(&query[..])
+        .bind(username)
+        .fetch_optional(&mut *conn).await?;
+
+    Ok(has_solves.unwrap_or_default())
+}
+
 pub async fn db_delete_user(db_connection: &DbConnection, user_id: i32) -> Result<bool, sqlx::Error> {
     let user = db_get_user_by_id(db_connection, user_id).await.unwrap_or(UserInstance::get_dead_guy_user());
     if user.id == -1 {
@@ -239,12 +286,19 @@ pub async fn db_delete_user(db_connection: &DbConnection, user_id: i32) -> Resul
         .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
 
     if result.rows_affected() > 0 {
+        db_remove_team_member(db_connection, user.username.to_owned()).await?;
+
         let query = format!("DELETE FROM {table_name} WHERE username = $1", table_name=DB_SOLVE_HISTORY_TABLE);
         let result: PgQueryResult = sqlx::query(&query[..])
             .bind(user.username.to_owned())
             .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
 
         if result.rows_affected() > 0 {
+            let query = format!("DELETE FROM {table_name} WHERE username = $1", table_name=DB_SOLVE_TABLE);
+            sqlx::query(&query[..])
+                .bind(user.username.to_owned())
+                .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
+
             let mut is_err = false;
             for chall_name in user.challenge_solved {
                 let query = format!("UPDATE {table_name} SET solved_by = array_remove(solved_by, $1) WHERE challenge_name = $2", table_name=DB_CHALLENGE_TABLE);
@@ -257,18 +311,7 @@ pub async fn db_delete_user(db_connection: &DbConnection, user_id: i32) -> Resul
                     is_err = true;
                 }
 
-                let chall = db_connection.get_challenge_by_name(chall_name.to_owned()).await;
-                let score = (MINIMUM_SCORE - INITIAL_SCORE)/(DECAY_VALUE);
-                let score = score * (i32::try_from((chall.solved_by.len()-1).pow(2)).unwrap() - SOLVES_BEFORE_DECAY);
-                let score = score + INITIAL_SCORE;
-
-                let query = format!("UPDATE {table_name} SET score = $1 WHERE challenge_name = $2", table_name=DB_CHALLENGE_TABLE);
-                let result: PgQueryResult = sqlx::query(&query[..])
-                    .bind(score)
-                    .bind(chall_name.to_owned())
-                    .execute(&db_connection.pool).await.unwrap_or(PgQueryResult::default());
-
-                if result.rows_affected() == 0 {
+                if !db_recompute_challenge_score(db_connection, chall_name.to_owned()).await {
                     is_err = true;
                 }
             }
@@ -0,0 +1,140 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::FromRow;
+use chrono::DateTime;
+use chrono::offset::Utc;
+
+use crate::database::{DbConnection, DB_WEBHOOK_TABLE, DB_WEBHOOK_DELIVERY_TABLE};
+
+#[derive(FromRow, Clone, serde::Deserialize, serde::Serialize)]
+pub struct WebhookData {
+    #[serde(default)]
+    pub id: i32,
+    pub url: String,
+    // discord, slack or generic
+    pub format: String,
+    // generic only, empty sends the event itself as JSON
+    #[serde(default)]
+    pub template: String,
+    // empty means the payload is not signed
+    #[serde(default)]
+    pub secret: String,
+    // empty means every topic the webhook module handles
+    #[serde(default)]
+    pub topics: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(FromRow, Clone)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub time: i64,
+    pub topic: String,
+    pub attempts: i32,
+    // 0 when the request never got an answer
+    pub status_code: i32,
+    pub success: bool,
+    pub error: String
+}
+
+impl WebhookDelivery {
+    pub fn new(webhook_id: i32, topic: String, attempts: i32, status_code: i32, success: bool, error: String) -> Self {
+        WebhookDelivery {
+            id: -1,
+            webhook_id,
+            time: chrono::offset::Utc::now().timestamp(),
+            topic,
+            attempts,
+            status_code,
+            success,
+            error
+        }
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.time, 0).unwrap_or(DateTime::from_timestamp(0, 0).unwrap())
+    }
+}
+
+pub async fn db_create_webhook(db_connection: &DbConnection, webhook: WebhookData) -> bool {
+    let query = format!("
+    INSERT INTO {table_name} (url, format, template, secret, topics, enabled)
+    VALUES ($1, $2, $3, $4, $5, $6);", table_name=DB_WEBHOOK_TABLE);
+
+    let res = sqlx::query(&query[..])
+        .bind(webhook.url.trim())
+        .bind(webhook.format)
+        .bind(webhook.template)
+        .bind(webhook.secret)
+        .bind(webhook.topics)
+        .bind(webhook.enabled)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+pub async fn db_delete_webhook(db_connection: &DbConnection, webhook_id: i32) -> bool {
+    let query = format!("DELETE FROM {table_name} WHERE id = $1;", table_name=DB_WEBHOOK_TABLE);
+
+    let res = sqlx::query(&query[..])
+        .bind(webhook_id)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+pub async fn db_get_all_webhooks(db_connection: &DbConnection) -> Vec<WebhookData> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY id ASC", table_name=DB_WEBHOOK_TABLE);
+
+    sqlx::query_as(&query[..])
+        .fetch_all(&db_connection.pool).await.unwrap_or_default()
+}
+
+pub async fn db_get_webhook_by_id(db_connection: &DbConnection, webhook_id: i32) -> Option<WebhookData> {
+    let query = format!("SELECT * FROM {table_name} WHERE id = $1", table_name=DB_WEBHOOK_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(webhook_id)
+        .fetch_optional(&db_connection.pool).await.unwrap_or_default()
+}
+
+pub async fn db_get_webhooks_for_topic(db_connection: &DbConnection, topic: &str) -> Vec<WebhookData> {
+    let query = format!("
+    SELECT * FROM {table_name}
+    WHERE enabled = true AND (cardinality(topics) = 0 OR $1 = ANY(topics))", table_name=DB_WEBHOOK_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(topic)
+        .fetch_all(&db_connection.pool).await.unwrap_or_default()
+}
+
+pub async fn db_log_webhook_delivery(db_connection: &DbConnection, delivery: WebhookDelivery) -> bool {
+    let query = format!("
+    INSERT INTO {table_name} (webhook_id, time, topic, attempts, status_code, success, error)
+    VALUES ($1, $2, $3, $4, $5, $6, $7);", table_name=DB_WEBHOOK_DELIVERY_TABLE);
+
+    let res: PgQueryResult = sqlx::query(&query[..])
+        .bind(delivery.webhook_id)
+        .bind(delivery.time)
+        .bind(delivery.topic)
+        .bind(delivery.attempts)
+        .bind(delivery.status_code)
+        .bind(delivery.success)
+        .bind(delivery.error)
+        .execute(&db_connection.pool).await.unwrap_or_default();
+
+    res.rows_affected() > 0
+}
+
+pub async fn db_get_recent_webhook_deliveries(db_connection: &DbConnection, limit: i32) -> Vec<WebhookDelivery> {
+    let query = format!("SELECT * FROM {table_name} ORDER BY id DESC LIMIT $1", table_name=DB_WEBHOOK_DELIVERY_TABLE);
+
+    sqlx::query_as(&query[..])
+        .bind(limit)
+        .fetch_all(&db_connection.pool).await.unwrap_or_default()
+}
@@ -1,29 +1,56 @@
-use std::{collections::HashMap, fs::File, io::Write, process::Command, str::FromStr, sync::mpsc::{self, Receiver, Sender}, thread::spawn};
+use std::{sync::Arc, time::Duration};
 
 use rand::Rng;
 use uuid::Uuid;
-use tokio::runtime::Runtime;
+use futures_util::future::BoxFuture;
+use tokio::{fs, process::Command, sync::Mutex};
+
+use crate::{database::{hint::HintData, DbConnection}, module::Module, notifier::{NotifierMessage, NotifierSender, Responder}, supervisor::Supervisor};
+use crate::events::DomainEvent;
+use crate::flag_receiver::{rules::{FlagRules, FLAG_PREFIX}, FlagReceiverCommand};
+use crate::scoring::ScoringConfig;
+use crate::timer::TimerCommand;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum DeployerCommand {
+    Schedule { challenge_filename: String, start_time: i128, end_time: i128, reply: Responder<DeployerReply> },
+    Deploy { challenge_filename: String, reply: Responder<DeployerReply> },
+    Destroy { challenge_filename: String, reply: Responder<DeployerReply> }
+}
+
+// unpacking and building an image can take a while
+pub const DEPLOYER_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
 
-use crate::{database::DbConnection, notifier::{self, craft_type_notify_message, NotifierCommInfo}, Notifier};
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum DeployerReply {
+    Scheduled,
+    Deployed,
+    Destroyed,
+    Failed(String)
+}
 
 #[derive(Clone)]
 struct Challenge {
     challenge_filename: String,
     challenge_image: String,
-    flag: String,
+    flag_rules: FlagRules,
     port: u16
 }
 
-struct DeployerCtx {
+pub(crate) struct DeployerCtx {
     // main comm channel
-    sender: Sender<(String, Vec<u8>)>,
-    listener: Receiver<Vec<u8>>,
+    sender: NotifierSender,
     
     db_conn: DbConnection,
+    // commands run concurrently, so everything they share lives behind a lock
+    state: Mutex<DeployerState>
+}
+
+struct DeployerState {
     challenges: Vec<Challenge>
 }
 
-impl DeployerCtx {
+impl DeployerState {
     fn is_port_used(&self, port: u16) -> bool {
         for challenge in &self.challenges {
             if challenge.port == port {
@@ -33,7 +60,13 @@ impl DeployerCtx {
         return false;
     }
 
-    fn set_challenge_port(&mut self, challenge_filename: &String, port: u16) {
+    // scheduling a challenge again replaces what the last schedule built
+    fn add_challenge(&mut self, challenge: Challenge) {
+        self.challenges.retain(|other| other.challenge_filename != challenge.challenge_filename);
+        self.challenges.push(challenge);
+    }
+
+    fn set_challenge_port(&mut self, challenge_filename: &String, port: u16) -> bool {
         let mut exist: bool = false;
         for challenge in self.challenges.iter_mut() {
             if challenge.challenge_filename == challenge_filename.to_string() {
@@ -41,198 +74,352 @@ impl DeployerCtx {
                 exist = true;
             }
         }
+        exist
+    }
 
-        if !exist { panic!("something went wrong! must not reach here!") };
+    fn get_challenge(&self, challenge_filename: &String) -> Option<Challenge> {
+        self.challenges.iter().find(|challenge| &challenge.challenge_filename == challenge_filename).cloned()
     }
+}
 
-    fn get_challenge(&mut self, challenge_filename: &String) -> Challenge {
-        for challenge in self.challenges.clone() {
-            if challenge.challenge_filename == challenge_filename.to_string() {
-                return challenge;
-            }
+impl DeployerCtx {
+    pub(crate) fn new(my_sender: NotifierSender, db_conn: DbConnection) -> Self {
+        DeployerCtx {
+            sender: my_sender,
+            db_conn,
+            state: Mutex::new(DeployerState { challenges: Vec::new() }),
         }
+    }
+}
 
-        panic!("something went wrong! must not reach here!")
+impl Module for DeployerCtx {
+    fn name(&self) -> &'static str {
+        "deployer"
+    }
+
+    fn topics(&self) -> Vec<&'static str> {
+        vec!["deployer"]
+    }
+
+    // the challenge list only lives in memory, after a restart it comes back from the schedule in the database
+    fn start<'a>(self: Arc<Self>, _supervisor: &'a Supervisor) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            restore_challenges(&self).await;
+        })
+    }
+
+    fn handle(self: Arc<Self>, message: NotifierMessage) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let cmd = match message {
+                NotifierMessage::Deployer(cmd) => cmd,
+                other => {
+                    println!("deployer ignoring message on {}", other.topic());
+                    return;
+                }
+            };
+
+            // a slow docker build must not hold up every other command
+            tokio::spawn(async move {
+                match cmd {
+                    DeployerCommand::Deploy { challenge_filename, reply } => reply.respond(cmd_deploy(&self, &challenge_filename).await),
+                    DeployerCommand::Schedule { challenge_filename, start_time, end_time, reply } => reply.respond(cmd_schedule(&self, &challenge_filename, start_time, end_time).await),
+                    DeployerCommand::Destroy { challenge_filename, reply } => reply.respond(cmd_destroy(&self, &challenge_filename).await)
+                }
+            });
+        })
     }
 }
 
-pub fn init(notifier: &mut Notifier, my_sender: Sender<(String, Vec<u8>)>, db_conn: DbConnection) {
-    let (notifier_sender, my_receiver) : (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
-    let ctx = DeployerCtx {
-        sender: my_sender,
-        listener: my_receiver,
-        db_conn,
-        challenges: Vec::new(),
+async fn cmd_schedule(ctx: &DeployerCtx, challenge_filename: &String, start_time: i128, end_time: i128) -> DeployerReply {
+    let (challenge, hints, scoring) = match prepare_challenge(challenge_filename).await {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            ctx.db_conn.set_challenge_schedule(challenge_filename.to_string(), 0, 0).await;
+            println!("{}", err);
+            publish_deploy_failed(ctx, challenge_filename, &err);
+            return DeployerReply::Failed(err);
+        }
     };
+    ctx.state.lock().await.add_challenge(challenge);
 
-    
-    let comm_info = NotifierCommInfo {
-        // id: Uuid::new_v4().as_u128(),
-        name: "deployer".to_string(),
-        broadcast_channel: notifier_sender
-    };
-    notifier.slaves.comm_infos.push(comm_info);
+    // scheduling again must not duplicate the hints from the archive
+    let existing_hints = ctx.db_conn.get_hints_for_challenge(challenge_filename.to_string()).await;
+    for hint in hints {
+        if !existing_hints.iter().any(|existing| existing.content == hint.content) {
+            ctx.db_conn.create_hint(HintData { challenge_name: challenge_filename.to_string(), ..hint }).await;
+        }
+    }
 
+    // without scoring.json the challenge keeps what it had, the event's scoring by default
+    if scoring.is_some() {
+        ctx.db_conn.set_challenge_scoring(challenge_filename.to_string(), scoring).await;
+    }
+    if ctx.db_conn.recompute_challenge_score(challenge_filename.to_string()).await {
+        ctx.sender.send(DomainEvent::ScoreChanged { reason: format!("scoring of {} updated", challenge_filename) });
+    }
 
-    spawn(move || {
-        deployer_loop(ctx)
+    ctx.sender.send(TimerCommand::Enqueue {
+        challenge_name: challenge_filename.to_string(),
+        start_time,
+        end_time
     });
+
+    // publish the release slot so it shows up on the upcoming page
+    ctx.db_conn.set_challenge_schedule(challenge_filename.to_string(), start_time as i64, end_time as i64).await;
+    DeployerReply::Scheduled
 }
 
-fn deployer_loop(mut ctx: DeployerCtx) {
-    loop {
-        let serialized_data = ctx.listener.recv().expect("deployer channel communication exited");
-        let data = deserialize_data(&serialized_data);
-        match data.get("cmd").expect("missing cmd").as_str() {
-            "deploy" => cmd_deploy(&mut ctx, &data),
-            "schedule" => cmd_schedule(&mut ctx, &data),
-            "destroy" => cmd_destroy(&mut ctx, &data),
-            _ => panic!("unknown cmd")
-        }
+// unpacks, reads the config files and builds the image, the challenge is ready to deploy afterwards
+async fn prepare_challenge(challenge_filename: &String) -> Result<(Challenge, Vec<HintData>, Option<ScoringConfig>), String> {
+    if !unpack_challenge(challenge_filename).await {
+        return Err("unpack failed".to_string());
     }
-}
 
-fn cmd_schedule(ctx: &mut DeployerCtx, data: &HashMap<&str, String>) {
+    let (flag_rules, hints, scoring) = load_challenge_config(challenge_filename).await?;
+    let (build_success, challenge_image) = build_challenge(challenge_filename).await;
+    if !build_success {
+        return Err("build failed".to_string());
+    }
 
-    let challenge_filename = data.get("challenge_filename").expect("missing challenge_filename");
-    let start_time = data.get("start_time").expect("missing start_time");
-    let end_time = data.get("end_time").expect("missing end_time");
+    let challenge_image = String::from_utf8(challenge_image)
+                                        .expect("failed converting docker image name")
+                                        .trim().replace("sha256:", "");
 
-        let unpack_success = unpack_challenge(challenge_filename);
+    Ok((Challenge {
+        challenge_image,
+        challenge_filename: challenge_filename.to_string(),
+        flag_rules,
+        port: 0,
+    }, hints, scoring))
+}
 
-        if unpack_success {
-            let flag = generate_challenge_flag(challenge_filename);
-            let (build_success, challenge_image) = build_challenge(challenge_filename);
-            if build_success {
+// running challenges only need their port back so it isn't handed out twice, the flag receiver restores their flags.
+// challenges waiting for their release are built again, docker's cache makes that quick
+async fn restore_challenges(ctx: &DeployerCtx) {
+    let mut challenges = Vec::new();
+
+    for pending in ctx.db_conn.get_pending_challenges().await {
+        if pending.running {
+            let port = pending.connection_string.rsplit(' ').next().and_then(|port| port.parse().ok()).unwrap_or(0);
+            challenges.push(Challenge {
+                challenge_filename: pending.challenge_name,
+                challenge_image: String::new(),
+                flag_rules: FlagRules::default(),
+                port
+            });
+            continue;
+        }
 
-                let challenge_image = String::from_utf8(challenge_image)
-                                                    .expect("failed converting docker image name")
-                                                    .trim().replace("sha256:", "");
-                
-                ctx.challenges.push(Challenge {
-                    challenge_image,
-                    challenge_filename: challenge_filename.to_string(),
-                    flag,
-                    port: 0,
-                });
+        match prepare_challenge(&pending.challenge_name).await {
+            Ok((challenge, _, _)) => challenges.push(challenge),
+            Err(err) => {
+                println!("can't restore {}: {}", pending.challenge_name, err);
+                ctx.db_conn.set_challenge_schedule(pending.challenge_name.clone(), 0, 0).await;
+                publish_deploy_failed(ctx, &pending.challenge_name, &err);
+            }
+        }
+    }
 
-                let target_module = String::from("timer");
-                let data = craft_type_notify_message(&target_module, &["enqueue", &challenge_filename.to_string(), &start_time.to_string(), &end_time.to_string()]);
-                ctx.sender.send((target_module, data)).expect("deployer cannot send");
+    println!("restored {} challenges", challenges.len());
+    ctx.state.lock().await.challenges = challenges;
+}
 
-            } else {
-                println!("build failed");
+async fn cmd_deploy(ctx: &DeployerCtx, challenge_filename: &String) -> DeployerReply {
+    let challenge = {
+        let mut state = ctx.state.lock().await;
+        let mut rng = rand::thread_rng();
+        let mut port: u16 = rng.gen_range(0x1000..0xffff);
+        loop {
+            if state.is_port_used(port) {
+                port = rng.gen_range(0x1000..0xffff);
             }
-
+            else {
+                break
+            }
+        }
+        if state.set_challenge_port(challenge_filename, port) {
+            state.get_challenge(challenge_filename)
         } else {
-            println!("unpack failed");
+            None
         }
-}
-
-fn cmd_deploy(ctx: &mut DeployerCtx, data: &HashMap<&str, String>) {
-    let challenge_filename = data.get("challenge_filename").expect("missing challenge_filename");
-    let rt = Runtime::new().expect("failed creating tokio runtime");
+    };
 
-    let mut rng = rand::thread_rng();
-    let mut port: u16 = rng.gen_range(0x1000..0xffff);
-    loop {
-        if ctx.is_port_used(port) {
-            port = rng.gen_range(0x1000..0xffff);
+    let challenge = match challenge {
+        Some(challenge) => challenge,
+        None => {
+            ctx.sender.send(TimerCommand::DeployInfo {
+                challenge_name: challenge_filename.to_string(),
+                success: false
+            });
+            println!("Deploy failed {}, it is not scheduled", challenge_filename);
+            publish_deploy_failed(ctx, challenge_filename, "challenge is not scheduled");
+            return DeployerReply::Failed("challenge is not scheduled".to_string());
         }
-        else {
-            break
-        }
-    }
-    ctx.set_challenge_port(challenge_filename, port);
-    let challenge = ctx.get_challenge(challenge_filename);
-    
-    let deploy_success = deploy_challenge(&challenge.challenge_filename, &challenge.challenge_image, challenge.port);
+    };
+
+    let deploy_success = deploy_challenge(&challenge.challenge_filename, &challenge.challenge_image, challenge.port, challenge.flag_rules.secret.as_deref()).await;
 
     if deploy_success {
 
-        let target_module = String::from_str("flag_receiver").unwrap();
-        let cmd = String::from_str("flag_info").unwrap();
-        let data = notifier::craft_type_notify_message(&target_module, &[cmd, challenge_filename.to_string(), challenge.flag]);
-        ctx.sender.send((target_module, data)).expect("deployer cannot send");
+        ctx.sender.send(FlagReceiverCommand::FlagInfo {
+            challenge_name: challenge_filename.to_string(),
+            rules: challenge.flag_rules
+        });
         
         let conn_string = format!("nc localhost {}", challenge.port);
-        rt.block_on(ctx.db_conn.set_challenge_connection_string(challenge_filename.to_string(), conn_string));
-        rt.block_on(ctx.db_conn.set_challenge_running(challenge_filename.to_string(), true));
+        ctx.db_conn.set_challenge_connection_string(challenge_filename.to_string(), conn_string.clone()).await;
+        ctx.db_conn.set_challenge_running(challenge_filename.to_string(), true).await;
+
+        ctx.sender.send(DomainEvent::ChallengeReleased {
+            challenge_name: challenge_filename.to_string(),
+            connection_string: conn_string
+        });
 
         println!("Deploy success {}", challenge_filename);
+        DeployerReply::Deployed
     } else {
 
-        let target_module = String::from_str("timer").unwrap();
-        let cmd = String::from_str("deploy_info").unwrap();
-        let data = notifier::craft_type_notify_message(&target_module, &[cmd, challenge_filename.to_string(), "fail".to_string()]);
-        ctx.sender.send((target_module, data)).expect("deployer cannot send");
-        ctx.challenges.retain(|challenge| &challenge.challenge_filename != challenge_filename);
+        ctx.sender.send(TimerCommand::DeployInfo {
+            challenge_name: challenge_filename.to_string(),
+            success: false
+        });
+        ctx.state.lock().await.challenges.retain(|challenge| &challenge.challenge_filename != challenge_filename);
+        // or a restart would try it again
+        ctx.db_conn.set_challenge_schedule(challenge_filename.to_string(), 0, 0).await;
         println!("Deploy failed {}", challenge_filename);
+        publish_deploy_failed(ctx, challenge_filename, "deploy failed");
+        DeployerReply::Failed("deploy failed".to_string())
     }
 }
 
-fn cmd_destroy(ctx: &mut DeployerCtx, data: &HashMap<&str, String>) {
-    let challenge_filename = data.get("challenge_filename").expect("missing challenge_filename");
-    let rt = Runtime::new().expect("failed creating tokio runtime");
-    let destroy_success = destroy_challenge(challenge_filename); 
+async fn cmd_destroy(ctx: &DeployerCtx, challenge_filename: &String) -> DeployerReply {
+    let destroy_success = destroy_challenge(challenge_filename).await;
     if destroy_success {
-        let target_module = String::from_str("flag_receiver").unwrap();
-        let data = notifier::craft_type_notify_message(&target_module, &["cleanup", challenge_filename]);
-        ctx.sender.send((target_module, data)).expect("deployer cannot send");
-
-        ctx.challenges.retain(|challenge| &challenge.challenge_filename != challenge_filename);
-        rt.block_on(ctx.db_conn.set_challenge_running(challenge_filename.to_string(), false));
+        ctx.sender.send(FlagReceiverCommand::Cleanup {
+            challenge_name: challenge_filename.to_string()
+        });
+
+        ctx.state.lock().await.challenges.retain(|challenge| &challenge.challenge_filename != challenge_filename);
+        ctx.db_conn.set_challenge_running(challenge_filename.to_string(), false).await;
+        ctx.db_conn.end_challenge_schedule(challenge_filename.to_string()).await;
+
+        ctx.sender.send(DomainEvent::ChallengeRetired {
+            challenge_name: challenge_filename.to_string()
+        });
+        DeployerReply::Destroyed
     }
     else {
         println!("destroy failed");
+        DeployerReply::Failed("destroy failed".to_string())
     }
 }
 
-fn destroy_challenge(challenge_filename: &String) -> bool {
+fn publish_deploy_failed(ctx: &DeployerCtx, challenge_filename: &str, reason: &str) {
+    ctx.sender.send(DomainEvent::DeployFailed {
+        challenge_name: challenge_filename.to_string(),
+        reason: reason.to_string()
+    });
+}
+
+async fn destroy_challenge(challenge_filename: &str) -> bool {
     let output = Command::new("docker")
                                 .args(["rm", "-f", challenge_filename])
                                 .output()
+                                .await
                                 .expect("failed running bash shell");
     return output.status.success();
 }
 
-fn deserialize_data(serialized_data: &Vec<u8>) -> HashMap<&str, String> {
-    let data: HashMap<&str, String> = serde_json::from_slice(serialized_data.as_slice()).expect("deserialize failed!");
-    return data;
-}
-
-fn unpack_challenge(challenge_filename: &String) -> bool {
+async fn unpack_challenge(challenge_filename: &String) -> bool {
     let output = Command::new("tar")
                                 .args(["-xf", &format!("{}.tar.gz", challenge_filename), "--one-top-level"])
                                 .current_dir("./archives")
                                 .output()
+                                .await
                                 .expect("failed running bash shell");
     return output.status.success();
 }
 
-fn build_challenge(challenge_filename: &String) -> (bool, Vec<u8>) {
+async fn build_challenge(challenge_filename: &String) -> (bool, Vec<u8>) {
     let build_path = format!("./archives/{}/chall", &challenge_filename);
     let output = Command::new("docker")
                                 .args(["build", "-q", "."])
                                 .current_dir(format!("{}", build_path))
                                 .output()
+                                .await
                                 .expect("failed running bash shell");
     return (output.status.success(), output.stdout);
 }
 
-fn deploy_challenge(challenge_filename: &String, challenge_image: &String, port: u16) -> bool {
+// the flag secret lets challenges with per-player flags derive them, see rules::derive_flag
+async fn deploy_challenge(challenge_filename: &str, challenge_image: &str, port: u16, flag_secret: Option<&str>) -> bool {
     let portmap = format!("{}:5000", port);
+    let flag_env = format!("LIVECTF_FLAG_SECRET={}", flag_secret.unwrap_or_default());
     let output = Command::new("docker")
-                                .args(["run", "-p", &portmap, "-d", "--name", challenge_filename, "--privileged", challenge_image])
+                                .args(["run", "-p", &portmap, "-d", "--name", challenge_filename, "--privileged", "-e", &flag_env, challenge_image])
                                 .output()
+                                .await
                                 .expect("failed running bash shell");
     return output.status.success();
 }
 
-fn generate_challenge_flag(challenge_filename: &String) -> String {
-    let flag = format!("coslivectf{{{}}}", Uuid::new_v4());
+// everything authors can ship next to the Dockerfile, any invalid file fails the schedule
+async fn load_challenge_config(challenge_filename: &String) -> Result<(FlagRules, Vec<HintData>, Option<ScoringConfig>), String> {
+    Ok((load_flag_rules(challenge_filename).await?, load_hints(challenge_filename).await?, load_scoring(challenge_filename).await?))
+}
+
+// authors can ship chall/flag.json with their own FlagRules, everyone else gets a generated flag
+async fn load_flag_rules(challenge_filename: &String) -> Result<FlagRules, String> {
+    let rules_path = format!("./archives/{}/chall/flag.json", challenge_filename);
+
+    let mut rules: FlagRules = match fs::read_to_string(rules_path).await {
+        Ok(content) => serde_json::from_str(&content).map_err(|err| format!("invalid flag.json: {}", err))?,
+        Err(_) => FlagRules::default()
+    };
+    rules.validate()?;
+
+    if rules.dynamic && rules.secret.is_none() {
+        let secret: [u8; 32] = rand::thread_rng().gen();
+        rules.secret = Some(secret.iter().map(|byte| format!("{:02x}", byte)).collect());
+    }
+
+    if rules.is_empty() {
+        return Ok(FlagRules::from_flag(generate_challenge_flag(challenge_filename).await));
+    }
+    Ok(rules)
+}
+
+// optional chall/hints.json, a list of { "content", "cost", "release_after" }
+async fn load_hints(challenge_filename: &String) -> Result<Vec<HintData>, String> {
+    let hints_path = format!("./archives/{}/chall/hints.json", challenge_filename);
+
+    let hints: Vec<HintData> = match fs::read_to_string(hints_path).await {
+        Ok(content) => serde_json::from_str(&content).map_err(|err| format!("invalid hints.json: {}", err))?,
+        Err(_) => vec![]
+    };
+
+    if hints.iter().any(|hint| hint.content.trim().is_empty() || hint.cost < 0 || hint.release_after < 0) {
+        return Err("hints need content, and cost and release_after can't be negative".to_string());
+    }
+    Ok(hints)
+}
+
+// optional chall/scoring.json, a ScoringConfig
+async fn load_scoring(challenge_filename: &String) -> Result<Option<ScoringConfig>, String> {
+    let scoring_path = format!("./archives/{}/chall/scoring.json", challenge_filename);
+
+    let scoring: ScoringConfig = match fs::read_to_string(scoring_path).await {
+        Ok(content) => serde_json::from_str(&content).map_err(|err| format!("invalid scoring.json: {}", err))?,
+        Err(_) => return Ok(None)
+    };
+
+    scoring.strategy().validate().map_err(|err| format!("invalid scoring.json: {}", err))?;
+    Ok(Some(scoring))
+}
+
+async fn generate_challenge_flag(challenge_filename: &String) -> String {
+    let flag = format!("{}{{{}}}", FLAG_PREFIX, Uuid::new_v4());
     let flag_file_path = format!("./archives/{}/chall/dist/flag", challenge_filename);
-    let mut flag_file = File::create(flag_file_path).expect("failed creating flag file");
-    flag_file.write_all(flag.as_bytes()).unwrap();
+    fs::write(flag_file_path, flag.as_bytes()).await.expect("failed creating flag file");
     return flag;
 }
\ No newline at end of file
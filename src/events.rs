@@ -0,0 +1,38 @@
+// Things that happened, published once on the notifier and delivered to every module subscribed to their topic.
+// Publishers don't know (or care) who listens, side effects like webhooks or caches subscribe here.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum DomainEvent {
+    ChallengeReleased { challenge_name: String, connection_string: String },
+    ChallengeRetired { challenge_name: String },
+    FlagAccepted { challenge_name: String, username: String },
+    FirstBlood { challenge_name: String, username: String },
+    // one stage of a multi-stage challenge, solving the last one publishes FlagAccepted instead
+    StageSolved { challenge_name: String, stage: String, username: String },
+    HintUnlocked { challenge_name: String, username: String, cost: i32 },
+    UserRegistered { username: String },
+    DeployFailed { challenge_name: String, reason: String },
+    // free text from the admins, see sheep center
+    Announcement { message: String },
+    // scores moved without a solve, e.g. the scoring changed or a player left their team
+    ScoreChanged { reason: String },
+    // this process missed messages of the others, modules holding a copy of shared state reload it
+    MessagesLost
+}
+
+impl DomainEvent {
+    pub fn topic(&self) -> &'static str {
+        match self {
+            DomainEvent::ChallengeReleased { .. } => "challenge_released",
+            DomainEvent::ChallengeRetired { .. } => "challenge_retired",
+            DomainEvent::FlagAccepted { .. } => "flag_accepted",
+            DomainEvent::FirstBlood { .. } => "first_blood",
+            DomainEvent::StageSolved { .. } => "stage_solved",
+            DomainEvent::HintUnlocked { .. } => "hint_unlocked",
+            DomainEvent::UserRegistered { .. } => "user_registered",
+            DomainEvent::DeployFailed { .. } => "deploy_failed",
+            DomainEvent::Announcement { .. } => "announcement",
+            DomainEvent::ScoreChanged { .. } => "score_changed",
+            DomainEvent::MessagesLost => "messages_lost"
+        }
+    }
+}
@@ -1,69 +1,204 @@
-use std::{collections::HashMap, str::FromStr, sync::mpsc::{self, Receiver, Sender}, thread::spawn, collections::BTreeMap};
+use std::{collections::HashMap, sync::{Arc, Mutex}, collections::BTreeMap, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
-use tokio::runtime::Runtime;
+use actix_web::{web, http::header, HttpResponse, HttpRequest, cookie::Cookie};
+use futures_util::future::BoxFuture;
+use tokio::sync::RwLock;
 // use uuid::Uuid;
 
-use crate::{notifier::{craft_type_notify_message, NotifierCommInfo, NotifierComms}, Notifier};
-use crate::database::{solve_history::SolveHistoryEntry, DbConnection};
-use crate::web_interface::{get_jwt_claims, forbiden};
+use crate::{module::Module, notifier::{NotifierComms, NotifierMessage, NotifierSender, Responder}, scoreboard_cache::ScoreboardCache, supervisor::Supervisor};
+use crate::database::{flag_review::FlagReview, settings::{Settings, EVENT_END_SETTING, LOCKOUT_DURATION_SETTING, LOCKOUT_THRESHOLD_SETTING}, solve_history::SolveHistoryEntry, user::UserInstance, DbConnection};
+use crate::events::DomainEvent;
+use crate::web_interface::{get_jwt_claims, forbiden, get_error};
 
-struct FlagReceiverCtx {
-    // main comm channel
-    sender: Sender<(String, Vec<u8>)>,
-    listener: Receiver<Vec<u8>>,
+pub mod rate_limit;
+pub mod rules;
+pub mod vault;
 
-    challenge_infos: HashMap<String, String>,
-    db_conn: DbConnection
+use rules::{FlagMatch, FlagMatcher, FlagOwners, FlagRules};
+use vault::FlagVault;
+
+const FLAG_RECEIVER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum SubmitVerdict {
+    // bonus is the blood bonus of the first solvers, included in the score
+    Correct { challenge_name: String, points: i32, bonus: i32 },
+    Incorrect,
+    // one stage of a multi-stage challenge, points is its share of the challenge
+    StageSolved { challenge_name: String, stage: String, points: i32 },
+    AlreadySolved {
+        challenge_name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stage: Option<String>
+    },
+    ChallengeNotRunning { challenge_name: String },
+    // seconds until the next submission is accepted
+    RateLimited { retry_after: u64 },
+    // too many wrong flags in a row, seconds until the lock expires
+    AccountLocked { retry_after: u64 },
+    EventOver
 }
 
-pub(crate) fn init(notifier: &mut Notifier, my_sender: Sender<(String, Vec<u8>)>, db_conn: DbConnection) {
-    let (notifier_sender, my_receiver) : (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
-    let ctx = FlagReceiverCtx {
-        sender: my_sender,
-        listener: my_receiver,
-        challenge_infos: HashMap::new(),
-        db_conn
-    };
+impl SubmitVerdict {
+    fn message(&self) -> String {
+        match self {
+            SubmitVerdict::Correct { challenge_name, points, bonus: 0 } => format!("Correct flag for {}! +{} points", challenge_name, points),
+            SubmitVerdict::Correct { challenge_name, points, bonus } => format!("Correct flag for {}! +{} points and a +{} blood bonus", challenge_name, points, bonus),
+            SubmitVerdict::Incorrect => "Wrong flag".to_string(),
+            SubmitVerdict::StageSolved { challenge_name, stage, points } => format!("Correct flag for {} of {}! +{} points", stage, challenge_name, points),
+            SubmitVerdict::AlreadySolved { challenge_name, stage: Some(stage) } => format!("You already solved {} of {}", stage, challenge_name),
+            SubmitVerdict::AlreadySolved { challenge_name, stage: None } => format!("You already solved {}", challenge_name),
+            SubmitVerdict::ChallengeNotRunning { challenge_name } => format!("{} is not running anymore", challenge_name),
+            SubmitVerdict::RateLimited { retry_after } => format!("Too many submissions, try again in {}s", retry_after),
+            SubmitVerdict::AccountLocked { retry_after } => format!("Too many wrong flags, your account is locked for {}s", retry_after),
+            SubmitVerdict::EventOver => "The event is over".to_string()
+        }
+    }
+}
 
+#[derive(serde::Serialize)]
+struct SubmitResponse {
+    is_error: bool,
+    message: String,
+    verdict: SubmitVerdict,
+    // the player's total score after this submission
+    score: u64
+}
 
-    let comm_info = NotifierCommInfo {
-        // id: Uuid::new_v4().as_u128(),
-        name: "flag_receiver".to_string(),
-        broadcast_channel: notifier_sender
-    };
-    notifier.slaves.comm_infos.push(comm_info);
+#[derive(serde::Deserialize)]
+pub struct SubmissionData {
+    flag: String,
+    #[serde(default)]
+    challenge: Option<String>
+}
 
-    
-    spawn(move || {
-        flag_receiver_loop(ctx)
-    });
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum FlagReceiverCommand {
+    // `challenge` restricts the check to that challenge's flag
+    FlagSubmit { flag: String, challenge: Option<String>, submit_by: String, ip: String, reply: Responder<SubmitVerdict> },
+    FlagInfo { challenge_name: String, rules: FlagRules },
+    Cleanup { challenge_name: String }
 }
 
-fn flag_receiver_loop(mut ctx: FlagReceiverCtx) {
-    loop {
-        let serialized_data = ctx.listener.recv().expect("flag receiver channel communication exited");
-        println!("flag received recv()");
-        let data = deserialize_data(&serialized_data);
-        match data.get("cmd").expect("missing cmd").as_str() {
+pub(crate) struct FlagReceiverCtx {
+    // main comm channel
+    sender: NotifierSender,
+    // submissions are checked concurrently, flag lookups only need a read lock
+    challenge_infos: RwLock<HashMap<String, FlagMatcher>>,
+    // flags of cleaned up challenges, so late submissions get a proper verdict
+    retired_infos: RwLock<HashMap<String, FlagMatcher>>,
+    // per-player flags of running challenges derived so far, see check_shared_flag
+    flag_owners: Mutex<HashMap<String, FlagOwners>>,
+    vault: FlagVault,
+    db_conn: DbConnection
+}
 
-            "flag_info" => cmd_flag_info(&mut ctx, &data),
+impl FlagReceiverCtx {
+    pub(crate) fn new(my_sender: NotifierSender, db_conn: DbConnection, vault: FlagVault) -> Self {
+        FlagReceiverCtx {
+            sender: my_sender,
+            challenge_infos: RwLock::new(HashMap::new()),
+            retired_infos: RwLock::new(HashMap::new()),
+            flag_owners: Mutex::new(HashMap::new()),
+            vault,
+            db_conn
+        }
+    }
+}
 
-            "flag_submit" => cmd_flag_submit(&mut ctx, &data),
+impl Module for FlagReceiverCtx {
+    fn name(&self) -> &'static str {
+        "flag_receiver"
+    }
 
-            "cleanup" => cmd_cleanup(&mut ctx, &data),
+    fn topics(&self) -> Vec<&'static str> {
+        vec!["flag_receiver", "messages_lost"]
+    }
+
+    // flags of challenges deployed before a restart only live in the database
+    fn start<'a>(self: Arc<Self>, _supervisor: &'a Supervisor) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            restore_flags(&self).await;
+        })
+    }
 
-            _ => panic!("unknown cmd")
+    fn handle(self: Arc<Self>, message: NotifierMessage) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            println!("flag received recv()");
+            let cmd = match message {
+                NotifierMessage::FlagReceiver(cmd) => cmd,
+                // flag rules or cleanups of other processes may be among the lost messages
+                NotifierMessage::Event(DomainEvent::MessagesLost) => {
+                    restore_flags(&self).await;
+                    return;
+                },
+                other => {
+                    println!("flag receiver ignoring message on {}", other.topic());
+                    return;
+                }
+            };
+
+            match cmd {
+
+                FlagReceiverCommand::FlagInfo { challenge_name, rules } => cmd_flag_info(&self, challenge_name, rules).await,
+
+                FlagReceiverCommand::FlagSubmit { flag, challenge, submit_by, ip, reply } => {
+                    tokio::spawn(async move {
+                        reply.respond(cmd_flag_submit(&self, flag, challenge, submit_by, ip).await);
+                    });
+                },
+
+                FlagReceiverCommand::Cleanup { challenge_name } => cmd_cleanup(&self, challenge_name).await
+            };
+        })
+    }
+}
+
+// replaces the flags in memory with the ones stored with the challenges
+async fn restore_flags(ctx: &FlagReceiverCtx) {
+    let mut challenge_infos = HashMap::new();
+    let mut retired_infos = HashMap::new();
+
+    for (challenge_name, running, sealed_rules) in ctx.db_conn.get_sealed_flag_rules().await {
+        let matcher = match ctx.vault.open(&sealed_rules).map(FlagMatcher::new) {
+            Some(Ok(matcher)) => matcher,
+            _ => {
+                println!("can't restore flag rules of {}, wrong LIVECTF_FLAG_KEY?", challenge_name);
+                continue;
+            }
         };
+
+        if running {
+            challenge_infos.insert(challenge_name, matcher);
+        } else {
+            retired_infos.insert(challenge_name, matcher);
+        }
     }
+    println!("restored flags of {} running challenges", challenge_infos.len());
+
+    *ctx.challenge_infos.write().await = challenge_infos;
+    *ctx.retired_infos.write().await = retired_infos;
+    ctx.flag_owners.lock().expect("failed acquiring lock").clear();
 }
 
-fn deserialize_data(serialized_data: &Vec<u8>) -> HashMap<&str, String> {
-    let data: HashMap<&str, String> = serde_json::from_slice(serialized_data.as_slice()).expect("deserialize failed!");
-    return data;
+// takes the flag as a JSON or form body, so it never shows up in access logs or browser history
+pub async fn api_submit_flag(slaves: web::Data<NotifierComms>, scoreboard_cache: web::Data<ScoreboardCache>, req: HttpRequest, submission: web::Either<web::Json<SubmissionData>, web::Form<SubmissionData>>) -> Result<HttpResponse, actix_web::Error> {
+    let submission = submission.into_inner();
+    submit_flag(&slaves, &scoreboard_cache, &req, submission.flag, submission.challenge).await
 }
 
-pub async fn handle_submission(slaves: web::Data<NotifierComms>, path: web::Path<(String,)>, req: HttpRequest) -> Result<HttpResponse, actix_web::Error> {
+// deprecated: the flag ends up in logs and breaks on URL-unsafe characters, use POST /api/submit
+pub async fn handle_submission(slaves: web::Data<NotifierComms>, scoreboard_cache: web::Data<ScoreboardCache>, path: web::Path<(String,)>, req: HttpRequest) -> Result<HttpResponse, actix_web::Error> {
+    println!("deprecated flag submission through /submit/{{flag}}");
+
+    let mut response = submit_flag(&slaves, &scoreboard_cache, &req, path.into_inner().0, None).await?;
+    response.headers_mut().insert(header::HeaderName::from_static("deprecation"), header::HeaderValue::from_static("true"));
+    response.headers_mut().insert(header::LINK, header::HeaderValue::from_static("</api/submit>; rel=\"successor-version\""));
+    Ok(response)
+}
+
+async fn submit_flag(slaves: &NotifierComms, scoreboard_cache: &ScoreboardCache, req: &HttpRequest, flag: String, challenge: Option<String>) -> Result<HttpResponse, actix_web::Error> {
     let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
 
     let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or(BTreeMap::new());
@@ -74,58 +209,273 @@ pub async fn handle_submission(slaves: web::Data<NotifierComms>, path: web::Path
         return Ok(forbiden("Not authenticated"));
     }
 
-    let submitted_flag = &path.0;
-    
-    let target_module = String::from_str("flag_receiver").unwrap();
-    let data = craft_type_notify_message(&target_module, &["flag_submit", submitted_flag, username]);
-
-    slaves.notify(target_module, data);
-    return Ok(HttpResponse::Ok().body(format!("flag submitted successfully")));
-}
-
-fn cmd_flag_info(ctx: &mut FlagReceiverCtx, data: &HashMap<&str, String>) {
-    let challenge_name = data.get("challenge_name").expect("missing challenge_name").to_string();
-    let flag = data.get("flag").expect("missing flag").to_string();
-    ctx.challenge_infos.insert(challenge_name, flag);
-}
-
-fn cmd_flag_submit(ctx: &mut FlagReceiverCtx, data: &HashMap<&str, String>) {
-    let submitted_flag = data.get("flag").expect("missing flag").to_string();
-    let username = data.get("submit_by").expect("missing username").to_string();
-    let rt = Runtime::new().expect("failed creating tokio runtime");
-
-    for (challenge_name, flag) in &ctx.challenge_infos {
-        // TODO: add score to user and decay challenge's score
-        if &submitted_flag == flag {
-            let solve_history = SolveHistoryEntry::new(
-                username.clone(),
-                challenge_name.clone(),
-                true,
-                submitted_flag
-            );
-            
-            rt.block_on(ctx.db_conn.user_add_score(
-                username, 
-                challenge_name.clone()
-                )
-            );
-            
-            rt.block_on(ctx.db_conn.log_solve_result(solve_history));    
-            return;
+    let flag = flag.trim().to_string();
+    if flag.is_empty() {
+        return Ok(get_error("Missing flag"));
+    }
+
+    let challenge = challenge.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+    // honours Forwarded/X-Forwarded-For so the limit works behind the reverse proxy
+    let ip = req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string();
+
+    let verdict = slaves.request(|reply| FlagReceiverCommand::FlagSubmit {
+        flag,
+        challenge,
+        submit_by: username.to_string(),
+        ip,
+        reply
+    }, FLAG_RECEIVER_REQUEST_TIMEOUT).await;
+
+    let verdict = match verdict {
+        Ok(verdict) => verdict,
+        Err(err) => return Ok(get_error(&format!("Flag could not be checked: {}", err)))
+    };
+
+    let is_solve = matches!(verdict, SubmitVerdict::Correct { .. } | SubmitVerdict::StageSolved { .. });
+    // the solve event may still be on its way, the player sees the new score right away
+    if is_solve {
+        scoreboard_cache.invalidate().await;
+    }
+    let score = scoreboard_cache.get().await.user_score(username);
+
+    Ok(HttpResponse::Ok().json(SubmitResponse {
+        is_error: !is_solve,
+        message: verdict.message(),
+        verdict,
+        score
+    }))
+}
+
+async fn cmd_flag_info(ctx: &FlagReceiverCtx, challenge_name: String, rules: FlagRules) {
+    let sealed_rules = ctx.vault.seal(&rules);
+    let stage_weights = rules.stage_weights();
+
+    // the deployer validated the rules already, this only fails on a broken message
+    match FlagMatcher::new(rules) {
+        Ok(matcher) => {
+            // every process gets this message, they all store the same rules
+            if !ctx.db_conn.set_challenge_flag_rules(challenge_name.clone(), sealed_rules).await {
+                println!("failed storing flag rules of {}", challenge_name);
+            }
+            ctx.db_conn.set_challenge_stages(challenge_name.clone(), stage_weights).await;
+            ctx.retired_infos.write().await.remove(&challenge_name);
+            // a new secret makes the flags derived so far useless
+            ctx.flag_owners.lock().expect("failed acquiring lock").remove(&challenge_name);
+            ctx.challenge_infos.write().await.insert(challenge_name, matcher);
+        },
+        Err(err) => println!("ignoring flag rules of {}: {}", challenge_name, err)
+    }
+}
+
+async fn cmd_flag_submit(ctx: &FlagReceiverCtx, submitted_flag: String, challenge: Option<String>, username: String, ip: String) -> SubmitVerdict {
+    let settings = ctx.db_conn.get_settings().await;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs() as i64;
+    let event_end = settings.get(EVENT_END_SETTING);
+    if event_end > 0 && now > event_end {
+        return SubmitVerdict::EventOver;
+    }
+
+    let user = ctx.db_conn.get_user_by_name(username.clone()).await;
+    if user.is_locked_at(now) {
+        return SubmitVerdict::AccountLocked { retry_after: (user.lock_due_at - now) as u64 };
+    }
+
+    let rate_limited = rate_limit::check(&ctx.db_conn, &settings, &username, &ip).await;
+    if let Some(retry_after) = rate_limited {
+        return SubmitVerdict::RateLimited { retry_after };
+    }
+
+    let verdict = judge_flag(ctx, &settings, user, now, &username, &submitted_flag, challenge.as_deref()).await;
+    // a guess turned away by the cooldown was never checked
+    if !matches!(verdict, SubmitVerdict::RateLimited { .. }) {
+        log_submission(ctx, &username, &verdict, submitted_flag).await;
+    }
+    verdict
+}
+
+// what the flag is worth to the player, everything past the rate limits
+async fn judge_flag(ctx: &FlagReceiverCtx, settings: &Settings, user: UserInstance, now: i64, username: &str, submitted_flag: &str, challenge: Option<&str>) -> SubmitVerdict {
+    let username = username.to_string();
+    let identity = flag_identity(ctx, &username).await;
+    let solved_challenge = find_challenge(&*ctx.challenge_infos.read().await, submitted_flag, &identity, challenge);
+
+    // the cooldown is on the challenge the flag is for, whatever the client says. a wrong flag is a guess on
+    // the challenge the client named, or without one on every running challenge, so leaving it out doesn't help
+    let guessed_on: Vec<String> = match (&solved_challenge, challenge) {
+        (Some((challenge_name, _)), _) => vec![challenge_name.clone()],
+        (None, Some(challenge)) => vec![challenge.to_string()],
+        (None, None) => ctx.challenge_infos.read().await.keys().cloned().collect()
+    };
+    if let Some(retry_after) = rate_limit::check_cooldown(&ctx.db_conn, settings, &username, &guessed_on).await {
+        return SubmitVerdict::RateLimited { retry_after };
+    }
+
+    let (challenge_name, flag_match) = match solved_challenge {
+        Some(solved) => solved,
+        None => {
+            let retired_challenge = find_challenge(&*ctx.retired_infos.read().await, submitted_flag, &identity, challenge)
+                .map(|(challenge_name, _)| challenge_name);
+            if retired_challenge.is_none() {
+                check_shared_flag(ctx, submitted_flag, &username, &identity).await;
+            }
+
+            return match retired_challenge {
+                Some(challenge_name) => SubmitVerdict::ChallengeNotRunning { challenge_name },
+                None => record_wrong_flag(ctx, settings, user, now).await
+            };
         }
+    };
+
+    let stage = match flag_match {
+        FlagMatch::Stage(stage) => Some(stage),
+        FlagMatch::Challenge => None
+    };
+
+    ctx.db_conn.reset_wrong_flags(username.clone()).await;
+
+    let challenge = ctx.db_conn.get_challenge_by_name(challenge_name.clone()).await;
+    if !challenge.running {
+        return SubmitVerdict::ChallengeNotRunning { challenge_name };
+    }
+    // a solve by any teammate counts for the whole team
+    let teammates = ctx.db_conn.get_teammates(username.clone()).await;
+    if challenge.solved_by.iter().any(|solver| teammates.contains(solver)) {
+        return SubmitVerdict::AlreadySolved { challenge_name, stage };
     }
 
+    if let Some(stage) = stage {
+        if !ctx.db_conn.challenge_stage_solve(challenge_name.clone(), stage.clone(), username.clone()).await {
+            return SubmitVerdict::AlreadySolved { challenge_name, stage: Some(stage) };
+        }
+
+        let stages = ctx.db_conn.get_challenge_stages(challenge_name.clone()).await;
+        // the last stage solves the challenge itself, scored like any other solve below
+        if !stages.iter().all(|other| other.solved_by.iter().any(|solver| teammates.contains(solver))) {
+            let total_weight: i32 = stages.iter().map(|other| other.weight).sum();
+            let weight = stages.iter().find(|other| other.name == stage).map(|other| other.weight).unwrap_or_default();
+
+            ctx.sender.send(DomainEvent::StageSolved {
+                challenge_name: challenge_name.clone(),
+                stage: stage.clone(),
+                username
+            });
+            let points = challenge.score * weight / total_weight.max(1);
+            return SubmitVerdict::StageSolved { challenge_name, stage, points };
+        }
+    }
+
+    let solve = match ctx.db_conn.user_add_score(username.clone(), challenge_name.clone()).await {
+        Some(solve) => solve,
+        // lost a race against another submission of the same user
+        None => return SubmitVerdict::AlreadySolved { challenge_name, stage: None }
+    };
+
+    // the position comes from the solve transaction, two players racing can't both get it
+    if solve.position == 1 {
+        ctx.sender.send(DomainEvent::FirstBlood {
+            challenge_name: challenge_name.clone(),
+            username: username.clone()
+        });
+    }
+    ctx.sender.send(DomainEvent::FlagAccepted { challenge_name: challenge_name.clone(), username });
+
+    // solving decays the challenge, what the player gets is the score after their own solve
+    let points = ctx.db_conn.get_challenge_by_name(challenge_name.clone()).await.score;
+    SubmitVerdict::Correct { challenge_name, points, bonus: solve.bonus }
+}
+
+fn find_challenge(infos: &HashMap<String, FlagMatcher>, submitted_flag: &str, identity: &str, challenge: Option<&str>) -> Option<(String, FlagMatch)> {
+    infos.iter()
+        .filter(|(challenge_name, _)| challenge.is_none_or(|challenge| challenge == challenge_name.as_str()))
+        .find_map(|(challenge_name, matcher)| matcher.find_match(submitted_flag, identity).map(|flag_match| (challenge_name.clone(), flag_match)))
+}
+
+// teammates share their per-player flags, so they are derived from the team's name
+async fn flag_identity(ctx: &FlagReceiverCtx, username: &str) -> String {
+    match ctx.db_conn.get_team_of(username.to_string()).await {
+        Some(team) => team.name,
+        None => username.to_string()
+    }
+}
+
+// a wrong flag that is another player's or team's per-player flag means it got shared, the submitter
+// and the owner go up for review. flags are derived once per player or team, not on every guess
+async fn check_shared_flag(ctx: &FlagReceiverCtx, submitted_flag: &str, username: &str, identity: &str) {
+    if !ctx.challenge_infos.read().await.values().any(|matcher| matcher.is_dynamic()) {
+        return;
+    }
+
+    let identities = ctx.db_conn.get_flag_identities().await;
+    let challenge_infos = ctx.challenge_infos.read().await;
+
+    let leak = {
+        let mut flag_owners = ctx.flag_owners.lock().expect("failed acquiring lock");
+        challenge_infos.iter()
+            .filter(|(_, matcher)| matcher.is_dynamic())
+            .find_map(|(challenge_name, matcher)| {
+                let owners = flag_owners.entry(challenge_name.clone()).or_default();
+                owners.extend(matcher, &identities);
+                owners.owner_of(matcher, submitted_flag)
+                    .filter(|owner| owner.as_str() != identity)
+                    .map(|owner| (challenge_name.clone(), owner.clone()))
+            })
+    };
+    drop(challenge_infos);
+
+    if let Some((challenge_name, owner)) = leak {
+        println!("{} submitted the {} flag of {}", username, challenge_name, owner);
+        ctx.db_conn.create_flag_review(FlagReview::new(challenge_name, username.to_string(), owner, submitted_flag.to_string())).await;
+    }
+}
+
+// locks the account once the user sent too many wrong flags in a row
+async fn record_wrong_flag(ctx: &FlagReceiverCtx, settings: &Settings, mut user: UserInstance, now: i64) -> SubmitVerdict {
+    let threshold = settings.get(LOCKOUT_THRESHOLD_SETTING);
+    let streak = ctx.db_conn.record_wrong_flag(user.username.clone()).await;
+
+    if threshold <= 0 || streak < threshold {
+        return SubmitVerdict::Incorrect;
+    }
+
+    let duration = settings.get(LOCKOUT_DURATION_SETTING).max(0);
+    ctx.db_conn.reset_wrong_flags(user.username.clone()).await;
+    user.lock(now + duration);
+
+    if !ctx.db_conn.save_user_lock(&user).await {
+        println!("failed locking user {}", user.username);
+        return SubmitVerdict::Incorrect;
+    }
+
+    println!("locked user {} for {}s after {} wrong flags", user.username, duration, streak);
+    SubmitVerdict::AccountLocked { retry_after: duration as u64 }
+}
+
+// only solves are a success, the score history replays the stages logged with them
+async fn log_submission(ctx: &FlagReceiverCtx, username: &str, verdict: &SubmitVerdict, submitted_flag: String) {
+    let (challenge_name, stage) = match verdict {
+        SubmitVerdict::Correct { challenge_name, .. } => (Some(challenge_name), None),
+        SubmitVerdict::StageSolved { challenge_name, stage, .. } => (Some(challenge_name), Some(stage)),
+        SubmitVerdict::AlreadySolved { challenge_name, stage } => (Some(challenge_name), stage.as_ref()),
+        SubmitVerdict::ChallengeNotRunning { challenge_name } => (Some(challenge_name), None),
+        _ => (None, None)
+    };
+
     let solve_history = SolveHistoryEntry::new(
-        username,
-        String::from("None"),
-        false,
+        username.to_string(),
+        challenge_name.cloned().unwrap_or("None".to_string()),
+        matches!(verdict, SubmitVerdict::Correct { .. } | SubmitVerdict::StageSolved { .. }),
         submitted_flag
-    );
+    ).with_stage(stage.cloned().unwrap_or_default());
 
-    rt.block_on(ctx.db_conn.log_solve_result(solve_history));
+    ctx.db_conn.log_solve_result(solve_history).await;
 }
 
-fn cmd_cleanup(ctx: &mut FlagReceiverCtx, data: &HashMap<&str, String>) {
-    let challenge_name = data.get("challenge_name").expect("missing challenge_name").to_string();
-    ctx.challenge_infos.remove(&challenge_name).expect(&format!("no challenge to cleanup: {}", challenge_name));
-}
\ No newline at end of file
+async fn cmd_cleanup(ctx: &FlagReceiverCtx, challenge_name: String) {
+    ctx.flag_owners.lock().expect("failed acquiring lock").remove(&challenge_name);
+    match ctx.challenge_infos.write().await.remove(&challenge_name) {
+        Some(flag) => {
+            ctx.retired_infos.write().await.insert(challenge_name, flag);
+        },
+        None => println!("no challenge to cleanup: {}", challenge_name)
+    }
+}
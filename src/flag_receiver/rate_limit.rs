@@ -0,0 +1,94 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database::{settings::{Settings, CHALLENGE_COOLDOWN_SETTING, SUBMIT_IP_LIMIT_SETTING, SUBMIT_USER_LIMIT_SETTING, SUBMIT_WINDOW_SETTING}, DbConnection};
+
+// sliding window limits, kept in the database so every livectf process counts the same submissions.
+// records the submission, or returns the seconds to wait when a limit is hit
+pub(crate) async fn check(db_conn: &DbConnection, settings: &Settings, username: &str, ip: &str) -> Option<u64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_millis() as i64;
+    let window = settings.get(SUBMIT_WINDOW_SETTING).max(1) * 1000;
+
+    let limits = vec![
+        (format!("user:{}", username), settings.get(SUBMIT_USER_LIMIT_SETTING)),
+        (format!("ip:{}", ip), settings.get(SUBMIT_IP_LIMIT_SETTING))
+    ];
+    db_conn.check_submit_limits(&limits, window, now).await
+}
+
+// one guess per challenge and cooldown, on every challenge the guess was on.
+// records the guess, or returns the seconds to wait when one of them is cooling down
+pub(crate) async fn check_cooldown(db_conn: &DbConnection, settings: &Settings, username: &str, challenges: &[String]) -> Option<u64> {
+    let cooldown = settings.get(CHALLENGE_COOLDOWN_SETTING);
+    if cooldown <= 0 || challenges.is_empty() {
+        return None;
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_millis() as i64;
+    let limits: Vec<(String, i64)> = challenges.iter()
+        .map(|challenge| (cooldown_key(username, challenge), 1))
+        .collect();
+    db_conn.check_submit_limits(&limits, cooldown * 1000, now).await
+}
+
+// usernames and challenge names can both hold any separator, JSON keeps the pair apart
+fn cooldown_key(username: &str, challenge: &str) -> String {
+    format!("cooldown:{}", serde_json::json!([username, challenge]))
+}
+
+// hits are the epoch milliseconds of the submissions inside the window, oldest first
+pub fn limit_hit(hits: &[i64], limit: i64, window: i64, now: i64) -> Option<u64> {
+    if limit <= 0 || (hits.len() as i64) < limit {
+        return None;
+    }
+
+    // the window may hold more hits than the limit if it was lowered, the limit is lifted once the last `limit` hits start leaving
+    let oldest = hits.get(hits.len() - limit as usize)?;
+    Some(retry_after(oldest + window - now))
+}
+
+fn retry_after(wait_millis: i64) -> u64 {
+    (wait_millis as f64 / 1000.0).ceil().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn under_the_limit_passes() {
+        assert_eq!(limit_hit(&[], 3, 60_000, 100_000), None);
+        assert_eq!(limit_hit(&[90_000, 95_000], 3, 60_000, 100_000), None);
+    }
+
+    #[test]
+    fn zero_limit_is_unlimited() {
+        assert_eq!(limit_hit(&[1, 2, 3, 4], 0, 60_000, 100_000), None);
+        assert_eq!(limit_hit(&[1, 2, 3, 4], -1, 60_000, 100_000), None);
+    }
+
+    #[test]
+    fn waits_until_the_oldest_hit_leaves_the_window() {
+        // the hit at 50s leaves a 60s window at 110s
+        assert_eq!(limit_hit(&[50_000, 70_000, 90_000], 3, 60_000, 100_000), Some(10));
+    }
+
+    #[test]
+    fn waits_are_rounded_up_to_at_least_a_second() {
+        assert_eq!(limit_hit(&[40_001], 1, 60_000, 100_000), Some(1));
+        assert_eq!(limit_hit(&[40_000], 1, 60_000, 100_000), Some(1));
+        assert_eq!(limit_hit(&[41_500], 1, 60_000, 100_000), Some(2));
+    }
+
+    #[test]
+    fn lowered_limits_wait_for_enough_hits_to_leave() {
+        // with a limit of 2 the submission passes again once only one hit is left, the one at 90s
+        assert_eq!(limit_hit(&[50_000, 70_000, 90_000], 2, 60_000, 100_000), Some(30));
+    }
+
+    #[test]
+    fn cooldown_keys_keep_username_and_challenge_apart() {
+        assert_ne!(cooldown_key("a:b", "c"), cooldown_key("a", "b:c"));
+        assert_ne!(cooldown_key("a\",", "c"), cooldown_key("a", "\",c"));
+        assert_eq!(cooldown_key("alice", "pwn1"), r#"cooldown:["alice","pwn1"]"#);
+    }
+}
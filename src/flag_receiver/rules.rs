@@ -0,0 +1,389 @@
+use std::collections::{HashMap, HashSet};
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::Sha256;
+
+pub const FLAG_PREFIX: &str = "coslivectf";
+
+// how a challenge's flag is checked, authors ship it as chall/flag.json
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FlagRules {
+    // any of these is accepted
+    pub flags: Vec<String>,
+    // matched against the whole flag, after normalisation
+    pub regex: Option<String>,
+    pub case_insensitive: bool,
+    // accept the flag with or without the coslivectf{} wrapper, surrounding whitespace is ignored
+    pub strip_wrapper: bool,
+    // every team, or player without a team, gets their own flag, see derive_flag
+    pub dynamic: bool,
+    // key of the per-player flags, the deployer generates one when it's missing
+    pub secret: Option<String>,
+    // challenges in several steps, each stage has its own flags and is worth a share of the points
+    pub stages: Vec<FlagStage>
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FlagStage {
+    pub name: String,
+    // relative to the other stages of the challenge
+    pub weight: i32,
+    #[serde(flatten)]
+    pub rules: FlagRules
+}
+
+// what a submitted flag solved
+pub enum FlagMatch {
+    Challenge,
+    Stage(String)
+}
+
+impl FlagRules {
+    pub fn from_flag(flag: String) -> Self {
+        FlagRules { flags: vec![flag], ..Default::default() }
+    }
+
+    // rules without any flag, regex, per-player flags or stages get a generated flag from the deployer
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.regex.is_none() && !self.dynamic && self.stages.is_empty()
+    }
+
+    // what the event log gets to see
+    pub fn redacted(&self) -> Self {
+        FlagRules {
+            flags: self.flags.iter().map(|_| "<redacted>".to_string()).collect(),
+            regex: self.regex.as_ref().map(|_| "<redacted>".to_string()),
+            secret: self.secret.as_ref().map(|_| "<redacted>".to_string()),
+            stages: self.stages.iter().map(|stage| FlagStage { rules: stage.rules.redacted(), ..stage.clone() }).collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(regex) = &self.regex {
+            Regex::new(regex).map_err(|err| format!("invalid flag regex: {}", err))?;
+        }
+        if self.flags.iter().any(|flag| flag.trim().is_empty()) {
+            return Err("flags can't be empty".to_string());
+        }
+
+        if self.stages.is_empty() {
+            return Ok(());
+        }
+        if !self.flags.is_empty() || self.regex.is_some() || self.dynamic {
+            return Err("flags of a challenge with stages go inside the stages".to_string());
+        }
+        for (i, stage) in self.stages.iter().enumerate() {
+            if stage.name.trim().is_empty() || self.stages[..i].iter().any(|other| other.name == stage.name) {
+                return Err("every stage needs its own name".to_string());
+            }
+            if stage.weight <= 0 {
+                return Err(format!("stage {} needs a positive weight", stage.name));
+            }
+            if stage.rules.dynamic || !stage.rules.stages.is_empty() {
+                return Err(format!("stage {} can't have per-player flags or stages", stage.name));
+            }
+            if stage.rules.is_empty() {
+                return Err(format!("stage {} has no flag", stage.name));
+            }
+            stage.rules.validate()?;
+        }
+        Ok(())
+    }
+
+    // (name, weight) of every stage
+    pub fn stage_weights(&self) -> Vec<(String, i32)> {
+        self.stages.iter().map(|stage| (stage.name.clone(), stage.weight)).collect()
+    }
+}
+
+// FlagRules compiled once, when the flag receiver learns about the challenge
+pub struct FlagMatcher {
+    rules: FlagRules,
+    flags: Vec<String>,
+    regex: Option<Regex>,
+    stages: Vec<(String, FlagMatcher)>
+}
+
+impl FlagMatcher {
+    pub fn new(rules: FlagRules) -> Result<Self, String> {
+        rules.validate()?;
+        if rules.dynamic && rules.secret.is_none() {
+            return Err("per-player flags need a secret".to_string());
+        }
+
+        let regex = match &rules.regex {
+            Some(regex) => {
+                let regex = if rules.case_insensitive { format!("(?i)^(?:{})$", regex) } else { format!("^(?:{})$", regex) };
+                Some(Regex::new(&regex).map_err(|err| format!("invalid flag regex: {}", err))?)
+            },
+            None => None
+        };
+        let flags = rules.flags.iter().map(|flag| normalise(&rules, flag)).collect();
+        let stages = rules.stages.iter()
+            .map(|stage| Ok((stage.name.clone(), FlagMatcher::new(stage.rules.clone())?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(FlagMatcher { rules, flags, regex, stages })
+    }
+
+    // identity is who per-player flags are derived for, see flag_receiver::flag_identity
+    pub fn find_match(&self, submitted_flag: &str, identity: &str) -> Option<FlagMatch> {
+        if self.stages.is_empty() {
+            return self.matches(submitted_flag, identity).then_some(FlagMatch::Challenge);
+        }
+
+        self.stages.iter()
+            .find(|(_, stage)| stage.matches(submitted_flag, identity))
+            .map(|(name, _)| FlagMatch::Stage(name.clone()))
+    }
+
+    fn matches(&self, submitted_flag: &str, identity: &str) -> bool {
+        let submitted_flag = normalise(&self.rules, submitted_flag);
+
+        // every flag is compared so the timing doesn't tell which one was close
+        let static_match = self.flags.iter()
+            .fold(false, |matched, flag| constant_time_eq(flag.as_bytes(), submitted_flag.as_bytes()) | matched);
+
+        static_match
+            || self.regex.as_ref().is_some_and(|regex| regex.is_match(&submitted_flag))
+            || self.is_own_flag(&submitted_flag, identity)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.rules.dynamic
+    }
+
+    // the normalised per-player flag of identity, None for challenges without per-player flags
+    fn flag_of(&self, identity: &str) -> Option<String> {
+        match (&self.rules.secret, self.rules.dynamic) {
+            (Some(secret), true) => Some(normalise(&self.rules, &derive_flag(secret, identity))),
+            _ => None
+        }
+    }
+
+    fn is_own_flag(&self, normalised_flag: &str, identity: &str) -> bool {
+        self.flag_of(identity).is_some_and(|own_flag| constant_time_eq(own_flag.as_bytes(), normalised_flag.as_bytes()))
+    }
+}
+
+// per-player flags of one challenge by flag, each one is derived once so finding whose flag got shared is a lookup
+#[derive(Default)]
+pub struct FlagOwners {
+    by_flag: HashMap<String, String>,
+    derived_for: HashSet<String>
+}
+
+impl FlagOwners {
+    // derives the flags of identities seen for the first time
+    pub fn extend(&mut self, matcher: &FlagMatcher, identities: &[String]) {
+        for identity in identities {
+            if !self.derived_for.insert(identity.clone()) {
+                continue;
+            }
+            if let Some(flag) = matcher.flag_of(identity) {
+                self.by_flag.insert(flag, identity.clone());
+            }
+        }
+    }
+
+    pub fn owner_of(&self, matcher: &FlagMatcher, submitted_flag: &str) -> Option<&String> {
+        self.by_flag.get(&normalise(&matcher.rules, submitted_flag))
+    }
+}
+
+// challenges compute the same thing from LIVECTF_FLAG_SECRET and the name of the player's team,
+// or the player's username when they have none: coslivectf{ first 32 hex chars of HMAC-SHA256(secret, identity) }
+pub fn derive_flag(secret: &str, identity: &str) -> String {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(identity.as_bytes());
+
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{{{}}}", FLAG_PREFIX, &digest[..32])
+}
+
+fn normalise(rules: &FlagRules, flag: &str) -> String {
+    let mut flag = flag;
+    if rules.strip_wrapper {
+        flag = flag.trim();
+        if let Some(inner) = flag.strip_prefix(FLAG_PREFIX).and_then(|rest| rest.strip_prefix('{')).and_then(|rest| rest.strip_suffix('}')) {
+            flag = inner;
+        }
+    }
+
+    if rules.case_insensitive {
+        flag.to_lowercase()
+    } else {
+        flag.to_string()
+    }
+}
+
+// only the length leaks, never where the first difference is
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(rules: FlagRules) -> FlagMatcher {
+        FlagMatcher::new(rules).unwrap()
+    }
+
+    fn solves(matcher: &FlagMatcher, flag: &str) -> bool {
+        matches!(matcher.find_match(flag, "alice"), Some(FlagMatch::Challenge))
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_slices() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"flag", b"flag"));
+        assert!(!constant_time_eq(b"flag", b"flaG"));
+        assert!(!constant_time_eq(b"flag", b"flag2"));
+        assert!(!constant_time_eq(b"xlag", b"flag"));
+    }
+
+    #[test]
+    fn any_static_flag_matches() {
+        let matcher = matcher(FlagRules { flags: vec!["coslivectf{a}".to_string(), "coslivectf{b}".to_string()], ..Default::default() });
+
+        assert!(solves(&matcher, "coslivectf{a}"));
+        assert!(solves(&matcher, "coslivectf{b}"));
+        assert!(!solves(&matcher, "coslivectf{c}"));
+        assert!(!solves(&matcher, " coslivectf{a}"));
+        assert!(!solves(&matcher, "COSLIVECTF{A}"));
+    }
+
+    #[test]
+    fn case_insensitive_flags() {
+        let matcher = matcher(FlagRules { flags: vec!["coslivectf{Mixed}".to_string()], case_insensitive: true, ..Default::default() });
+
+        assert!(solves(&matcher, "COSLIVECTF{mixed}"));
+        assert!(!solves(&matcher, "coslivectf{mixe}"));
+    }
+
+    #[test]
+    fn wrapper_and_whitespace_are_optional_when_stripped() {
+        let matcher = matcher(FlagRules { flags: vec!["coslivectf{inner}".to_string()], strip_wrapper: true, ..Default::default() });
+
+        assert!(solves(&matcher, "coslivectf{inner}"));
+        assert!(solves(&matcher, "  inner\n"));
+        assert!(!solves(&matcher, "coslivectf{inner"));
+    }
+
+    #[test]
+    fn regex_matches_the_whole_flag() {
+        let matcher = matcher(FlagRules { regex: Some(r"coslivectf\{[0-9]+\}".to_string()), ..Default::default() });
+
+        assert!(solves(&matcher, "coslivectf{1234}"));
+        assert!(!solves(&matcher, "coslivectf{12a}"));
+        assert!(!solves(&matcher, "xcoslivectf{1234}"));
+        assert!(!solves(&matcher, "coslivectf{1234}x"));
+    }
+
+    #[test]
+    fn case_insensitive_regex() {
+        let matcher = matcher(FlagRules { regex: Some("coslivectf\\{abc\\}".to_string()), case_insensitive: true, ..Default::default() });
+
+        assert!(solves(&matcher, "CosLiveCTF{ABC}"));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(FlagMatcher::new(FlagRules { regex: Some("(".to_string()), ..Default::default() }).is_err());
+        assert!(FlagMatcher::new(FlagRules { flags: vec![" ".to_string()], ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn redacted_rules_hide_every_flag() {
+        let rules = FlagRules { flags: vec!["coslivectf{a}".to_string()], regex: Some("x".to_string()), secret: Some("k".to_string()), ..Default::default() };
+        let redacted = format!("{:?}", rules.redacted());
+
+        assert!(!redacted.contains("coslivectf{a}"));
+        assert!(!redacted.contains("\"k\""));
+        assert_eq!(rules.redacted().flags, vec!["<redacted>"]);
+    }
+
+    fn dynamic() -> FlagMatcher {
+        matcher(FlagRules { dynamic: true, secret: Some("s3cret".to_string()), ..Default::default() })
+    }
+
+    #[test]
+    fn derived_flags_are_hmac_of_the_identity() {
+        assert_eq!(derive_flag("s3cret", "team a"), "coslivectf{93f8551c67f6994e116b1692598b9b55}");
+        assert_ne!(derive_flag("s3cret", "team b"), derive_flag("s3cret", "team a"));
+    }
+
+    #[test]
+    fn only_the_own_per_player_flag_matches() {
+        let matcher = dynamic();
+        let flag = derive_flag("s3cret", "team a");
+
+        assert!(matches!(matcher.find_match(&flag, "team a"), Some(FlagMatch::Challenge)));
+        assert!(matcher.find_match(&flag, "team b").is_none());
+        assert!(matcher.is_dynamic());
+    }
+
+    #[test]
+    fn per_player_flags_need_a_secret() {
+        assert!(FlagMatcher::new(FlagRules { dynamic: true, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn owners_are_found_by_their_flag() {
+        let matcher = dynamic();
+        let mut owners = FlagOwners::default();
+        owners.extend(&matcher, &["team a".to_string(), "bob".to_string()]);
+
+        assert_eq!(owners.owner_of(&matcher, &derive_flag("s3cret", "bob")), Some(&"bob".to_string()));
+        assert_eq!(owners.owner_of(&matcher, &derive_flag("s3cret", "team a")), Some(&"team a".to_string()));
+        assert_eq!(owners.owner_of(&matcher, &derive_flag("s3cret", "carol")), None);
+
+        owners.extend(&matcher, &["carol".to_string()]);
+        assert_eq!(owners.owner_of(&matcher, &derive_flag("s3cret", "carol")), Some(&"carol".to_string()));
+    }
+
+    #[test]
+    fn static_flags_have_no_owners() {
+        let matcher = matcher(FlagRules::from_flag("coslivectf{a}".to_string()));
+        let mut owners = FlagOwners::default();
+        owners.extend(&matcher, &["alice".to_string()]);
+
+        assert_eq!(owners.owner_of(&matcher, "coslivectf{a}"), None);
+    }
+
+    fn stage(name: &str, weight: i32, flag: &str) -> FlagStage {
+        FlagStage { name: name.to_string(), weight, rules: FlagRules::from_flag(flag.to_string()) }
+    }
+
+    #[test]
+    fn stages_match_by_their_own_flags() {
+        let rules = FlagRules { stages: vec![stage("user", 1, "coslivectf{user}"), stage("root", 3, "coslivectf{root}")], ..Default::default() };
+        let matcher = matcher(rules.clone());
+
+        assert!(matches!(matcher.find_match("coslivectf{root}", "alice"), Some(FlagMatch::Stage(name)) if name == "root"));
+        assert!(matches!(matcher.find_match("coslivectf{user}", "alice"), Some(FlagMatch::Stage(name)) if name == "user"));
+        assert!(matcher.find_match("coslivectf{other}", "alice").is_none());
+        assert_eq!(rules.stage_weights(), vec![("user".to_string(), 1), ("root".to_string(), 3)]);
+    }
+
+    #[test]
+    fn invalid_stages_are_rejected() {
+        let with_stages = |stages: Vec<FlagStage>| FlagRules { stages, ..Default::default() }.validate();
+
+        assert!(with_stages(vec![stage("a", 1, "x"), stage("a", 1, "y")]).is_err());
+        assert!(with_stages(vec![stage(" ", 1, "x")]).is_err());
+        assert!(with_stages(vec![stage("a", 0, "x")]).is_err());
+        assert!(with_stages(vec![FlagStage { name: "a".to_string(), weight: 1, rules: FlagRules::default() }]).is_err());
+        assert!(FlagRules { flags: vec!["x".to_string()], stages: vec![stage("a", 1, "y")], ..Default::default() }.validate().is_err());
+        assert!(with_stages(vec![stage("a", 1, "x"), stage("b", 2, "y")]).is_ok());
+    }
+}
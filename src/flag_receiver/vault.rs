@@ -0,0 +1,74 @@
+use std::{env, fs};
+
+use aes_gcm::{aead::{Aead, KeyInit}, Aes256Gcm, Key, Nonce};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::flag_receiver::rules::FlagRules;
+
+// every livectf process sharing the database needs the same key
+const FLAG_KEY_ENV: &str = "LIVECTF_FLAG_KEY";
+// used when the env var is missing, created on first boot
+const FLAG_KEY_FILE: &str = "./flag.key";
+const NONCE_LENGTH: usize = 12;
+
+// encrypts flag rules before they are stored next to the challenge
+#[derive(Clone)]
+pub struct FlagVault {
+    cipher: Aes256Gcm
+}
+
+impl FlagVault {
+    pub fn load() -> Self {
+        let secret = match env::var(FLAG_KEY_ENV) {
+            Ok(secret) => secret,
+            Err(_) => match fs::read_to_string(FLAG_KEY_FILE) {
+                Ok(secret) => secret.trim().to_string(),
+                Err(_) => {
+                    let secret = to_hex(&rand::thread_rng().gen::<[u8; 32]>());
+                    fs::write(FLAG_KEY_FILE, &secret).expect("failed creating flag key file");
+                    println!("created flag key in {}, set {} to share it between processes", FLAG_KEY_FILE, FLAG_KEY_ENV);
+                    secret
+                }
+            }
+        };
+
+        let key = Sha256::digest(secret.as_bytes());
+        FlagVault { cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)) }
+    }
+
+    // hex of nonce followed by the ciphertext
+    pub fn seal(&self, rules: &FlagRules) -> String {
+        let plaintext = serde_json::to_vec(rules).expect("failed serializing flag rules");
+        let nonce: [u8; NONCE_LENGTH] = rand::thread_rng().gen();
+        let ciphertext = self.cipher.encrypt(Nonce::from_slice(&nonce), plaintext.as_slice()).expect("failed encrypting flag rules");
+
+        to_hex(&[nonce.as_slice(), ciphertext.as_slice()].concat())
+    }
+
+    // None when the data was tampered with or sealed with another key
+    pub fn open(&self, sealed: &str) -> Option<FlagRules> {
+        let sealed = from_hex(sealed)?;
+        if sealed.len() < NONCE_LENGTH {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let plaintext = self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+        serde_json::from_slice(&plaintext).ok()
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -1,32 +1,33 @@
-use std::sync::mpsc::{self, Receiver, Sender};
-
 use actix_web::{App, HttpServer, web};
 use database::user::UserInstance;
-use notifier::{Notifier, NotifierComms};
+use module::ModuleRegistry;
+use notifier::{transport::{LocalTransport, PostgresTransport, Transport}, NotifierComms};
+use supervisor::Supervisor;
+use std::{env, sync::Arc};
 use actix_files;
 
 mod challenge_upload_handler;
 mod deployer;
 mod database;
+mod events;
 mod web_interface;
 mod flag_receiver;
 mod timer;
+mod webhook;
+mod module;
 mod notifier;
+mod scoring;
+mod scoreboard_cache;
+mod site_modules;
+mod supervisor;
 mod utils;
-// ANYTHING RELATED TO NOTIFIER SHOULD BE CRITICAL, ABORT!!!
+// modules are restarted by the supervisor when they crash, the notifier itself must never die
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let (slave_sender, listen_master): (Sender<(String, Vec<u8>)>, Receiver<(String, Vec<u8>)>) = mpsc::channel();
-    let mut slaves = NotifierComms {
-        comm_infos: Vec::new()
-    };
-
-    let mut notifier = Notifier {
-        slaves: slaves,
-        listen_master: listen_master
-    };
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:31337";
 
+// modules and the web server share this one multi-threaded runtime
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
     let db_conn = database::new_db_connection().await.expect("can't open connection to database");
 
     let admin_username = uuid::Uuid::new_v4().to_string();
@@ -44,27 +45,39 @@ async fn main() -> std::io::Result<()> {
 
     println!("Admin credential is: \n\t{}\n\t{}", admin_username, secret_password);
 
-    challenge_upload_handler::init(&mut notifier, slave_sender.clone(), db_conn.clone());
-    deployer::init(&mut notifier, slave_sender.clone(), db_conn.clone());
-    flag_receiver::init(&mut notifier, slave_sender.clone(), db_conn.clone());
-    timer::init(&mut notifier, slave_sender.clone());
-    // database::init(&mut notifier, slave_sender.clone());
+    // LIVECTF_TRANSPORT=postgres lets several livectf processes share the database behind a load balancer
+    let transport: Arc<dyn Transport> = match env::var("LIVECTF_TRANSPORT").as_deref() {
+        Ok("postgres") => Arc::new(PostgresTransport::new(db_conn.clone())),
+        _ => Arc::new(LocalTransport)
+    };
+
+    let supervisor = Supervisor::new();
+    let mut registry = ModuleRegistry::new(supervisor.clone(), db_conn.clone(), transport);
+
+    registry.register(Arc::new(flag_receiver::FlagReceiverCtx::new(registry.sender("flag_receiver"), db_conn.clone(), flag_receiver::vault::FlagVault::load())));
+    registry.register_leader(Arc::new(deployer::DeployerCtx::new(registry.sender("deployer"), db_conn.clone())));
+    registry.register_leader(Arc::new(timer::TimerCtx::new(registry.sender("timer"), db_conn.clone())));
+    registry.register_leader(Arc::new(webhook::WebhookCtx::new(db_conn.clone())));
+    let scoreboard_cache = Arc::new(scoreboard_cache::ScoreboardCache::new(db_conn.clone()));
+    registry.register(scoreboard_cache.clone());
+    site_modules::register(&mut registry, db_conn.clone());
 
-    slaves = notifier.slaves.clone();
+    let (slaves, modules) = registry.start().await;
+    let result = webserver_loop(slaves, db_conn, supervisor, scoreboard_cache).await;
 
-    tokio::spawn(async move {
-        notifier.run();
-    });
-    return webserver_loop(slaves, db_conn).await;
+    modules.stop().await;
+    return result;
 }
 
-async fn webserver_loop(slaves: NotifierComms, db_conn: database::DbConnection) -> std::io::Result<()> {
+async fn webserver_loop(slaves: NotifierComms, db_conn: database::DbConnection, supervisor: Supervisor, scoreboard_cache: Arc<scoreboard_cache::ScoreboardCache>) -> std::io::Result<()> {
 
     // TODO: we need CSRF token to mitigate CSRF
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(slaves.clone()))
             .app_data(web::Data::new(db_conn.do_clone()))
+            .app_data(web::Data::new(supervisor.clone()))
+            .app_data(web::Data::from(Arc::clone(&scoreboard_cache)))
             .service(actix_files::Files::new("/static", "./static"))
             .service(actix_files::Files::new("/attachments", "./attachments"))
             .route("/", web::get().to(web_interface::index))
@@ -73,6 +86,9 @@ async fn webserver_loop(slaves: NotifierComms, db_conn: database::DbConnection)
             .route("/sheep_center", web::get().to(web_interface::admin_index))
             .route("/challenges", web::get().to(web_interface::challenges))
             .route("/scoreboard", web::get().to(web_interface::scoreboard))
+            .route("/team", web::get().to(web_interface::team_page))
+            .route("/upcoming", web::get().to(web_interface::upcoming))
+            .route("/upcoming.ics", web::get().to(web_interface::calendar::api_upcoming_ics))
             .route("/api/login", web::post().to(web_interface::user::api_user_login))
             .route("/api/register", web::post().to(web_interface::user::api_user_register))
             .route("/api/user/create", web::post().to(web_interface::user::api_user_create))
@@ -81,13 +97,35 @@ async fn webserver_loop(slaves: NotifierComms, db_conn: database::DbConnection)
             .route("/api/user/{user_id}", web::delete().to(web_interface::user::api_delete_user))
             .route("/api/user/search", web::get().to(web_interface::user::api_filter_user))
             .route("/api/challenge-upload", web::post().to(challenge_upload_handler::handle_challenge))
+            .route("/api/rounds", web::post().to(web_interface::challenge::api_plan_rounds))
+            .route("/api/event/{action}", web::post().to(web_interface::challenge::api_event_action))
+            .route("/api/webhooks", web::post().to(web_interface::webhook::api_webhook_create))
+            .route("/api/webhooks/{webhook_id}", web::delete().to(web_interface::webhook::api_webhook_delete))
+            .route("/api/webhooks/{webhook_id}/test", web::post().to(web_interface::webhook::api_webhook_test))
+            .route("/api/announce", web::post().to(web_interface::webhook::api_announce))
+            .route("/api/settings", web::post().to(web_interface::settings::api_settings_update))
+            .route("/api/scoring", web::post().to(web_interface::settings::api_scoring_update))
+            .route("/api/blood-bonus", web::post().to(web_interface::settings::api_blood_bonus_update))
+            .route("/api/hints", web::post().to(web_interface::hint::api_hint_create))
+            .route("/api/hints/{hint_id}", web::delete().to(web_interface::hint::api_hint_delete))
+            .route("/api/hints/{hint_id}/unlock", web::post().to(web_interface::hint::api_hint_unlock))
+            .route("/api/challenges/{challenge}/hints", web::get().to(web_interface::hint::api_challenge_hints))
+            .route("/api/teams", web::post().to(web_interface::team::api_team_create))
+            .route("/api/teams/join", web::post().to(web_interface::team::api_team_join))
+            .route("/api/teams/leave", web::post().to(web_interface::team::api_team_leave))
+            .route("/api/teams/kick", web::post().to(web_interface::team::api_team_kick))
+            .route("/api/teams/invite", web::post().to(web_interface::team::api_team_invite))
+            .route("/api/teams/{team_id}", web::delete().to(web_interface::team::api_team_delete))
+            .route("/api/scoreboard/history", web::get().to(web_interface::scoreboard::api_score_history))
+            .route("/api/scoreboard/history/{username}", web::get().to(web_interface::scoreboard::api_user_score_history))
+            .route("/api/submit", web::post().to(flag_receiver::api_submit_flag))
             .route("/submit/{flag}", web::post().to(flag_receiver::handle_submission))
             .route("/api/{challenge}/{action}", web::post().to(web_interface::challenge::api_challenge_action))
             .default_service(
                 web::route().to(web_interface::not_found)
             )
     })
-    .bind("127.0.0.1:31337")?
+    .bind(env::var("LIVECTF_BIND").unwrap_or(DEFAULT_BIND_ADDRESS.to_string()))?
     .run()
     .await
 
@@ -0,0 +1,179 @@
+use std::sync::{Arc, RwLock};
+
+use futures_util::future::BoxFuture;
+use tokio::sync::{mpsc::{self, UnboundedReceiver, UnboundedSender}, Mutex};
+
+use crate::database::DbConnection;
+use crate::notifier::{transport::Transport, Envelope, Notifier, NotifierCommInfo, NotifierComms, NotifierMessage, NotifierSender};
+use crate::supervisor::Supervisor;
+
+// A module is anything that reacts to notifier messages. Register it with the ModuleRegistry,
+// the registry wires up its channel, routes its topics to it and keeps it alive through the supervisor.
+pub trait Module: Send + Sync + 'static {
+    // unique, shows up in the logs and on the modules page
+    fn name(&self) -> &'static str;
+
+    // topics (see NotifierMessage::topic) this module wants delivered
+    fn topics(&self) -> Vec<&'static str>;
+
+    // called for every message on a subscribed topic, one message at a time, long work should be spawned
+    fn handle(self: Arc<Self>, message: NotifierMessage) -> BoxFuture<'static, ()>;
+
+    // called once before the first message, background tasks get started here
+    fn start<'a>(self: Arc<Self>, _supervisor: &'a Supervisor) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+
+    // called when the server shuts down, and for leader modules when this process stops being the leader.
+    // background tasks started in start have to stop here, start is called again if the process leads again
+    fn stop(self: Arc<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async {})
+    }
+}
+
+pub struct ModuleRegistry {
+    notifier: Notifier,
+    // master channel, modules use it to talk to each other through the notifier
+    master: UnboundedSender<Envelope>,
+    supervisor: Supervisor,
+    modules: Vec<Arc<dyn Module>>,
+    // only run on the elected leader, see Transport::acquire_leadership
+    leader_modules: Vec<Arc<dyn Module>>
+}
+
+pub struct RunningModules {
+    modules: Arc<Mutex<Vec<Arc<dyn Module>>>>
+}
+
+impl ModuleRegistry {
+    pub fn new(supervisor: Supervisor, db_conn: DbConnection, transport: Arc<dyn Transport>) -> Self {
+        let (master, listen_master): (UnboundedSender<Envelope>, UnboundedReceiver<Envelope>) = mpsc::unbounded_channel();
+
+        ModuleRegistry {
+            notifier: Notifier {
+                listen_master,
+                comm_infos: Arc::new(RwLock::new(Vec::new())),
+                db_conn,
+                transport
+            },
+            master,
+            supervisor,
+            modules: Vec::new(),
+            leader_modules: Vec::new()
+        }
+    }
+
+    // `source` is what the event log records as the sender, use the module's name
+    pub fn sender(&self, source: &str) -> NotifierSender {
+        NotifierSender::new(source, self.master.clone())
+    }
+
+    // runs on every livectf process
+    pub fn register(&mut self, module: Arc<dyn Module>) {
+        if self.is_registered(module.name()) {
+            println!("module {} is already registered, ignoring", module.name());
+            return;
+        }
+        self.modules.push(module);
+    }
+
+    // runs on exactly one livectf process, for modules owning shared state like containers or the schedule
+    pub fn register_leader(&mut self, module: Arc<dyn Module>) {
+        if self.is_registered(module.name()) {
+            println!("module {} is already registered, ignoring", module.name());
+            return;
+        }
+        self.leader_modules.push(module);
+    }
+
+    fn is_registered(&self, name: &str) -> bool {
+        self.modules.iter().chain(self.leader_modules.iter()).any(|registered| registered.name() == name)
+    }
+
+    // start every registered module and the notifier, returns the handle web handlers use to reach the modules.
+    // leader modules start whenever this process wins the election, which may be never,
+    // and stop when it loses the leadership, so two processes never run them at once.
+    pub async fn start(self) -> (NotifierComms, RunningModules) {
+        let running: Arc<Mutex<Vec<Arc<dyn Module>>>> = Arc::new(Mutex::new(vec![]));
+
+        for module in self.modules {
+            start_module(&self.supervisor, &self.notifier.comm_infos, module, &running).await;
+        }
+
+        let supervisor = self.supervisor.clone();
+        let comm_infos = Arc::clone(&self.notifier.comm_infos);
+        let transport = Arc::clone(&self.notifier.transport);
+        let leader_modules = self.leader_modules;
+        let leader_running = Arc::clone(&running);
+        tokio::spawn(async move {
+            if leader_modules.is_empty() {
+                return;
+            }
+
+            loop {
+                transport.acquire_leadership().await;
+                for module in &leader_modules {
+                    start_module(&supervisor, &comm_infos, Arc::clone(module), &leader_running).await;
+                }
+
+                transport.leadership_lost().await;
+                println!("lost the leadership, stopping leader modules");
+                for module in &leader_modules {
+                    stop_module(&supervisor, &comm_infos, Arc::clone(module), &leader_running).await;
+                }
+            }
+        });
+
+        let slaves = NotifierComms { sender: NotifierSender::new("web_interface", self.master.clone()) };
+        tokio::spawn(self.notifier.run());
+
+        (slaves, RunningModules { modules: running })
+    }
+}
+
+async fn start_module(supervisor: &Supervisor, comm_infos: &RwLock<Vec<NotifierCommInfo>>, module: Arc<dyn Module>, running: &Mutex<Vec<Arc<dyn Module>>>) {
+    let (notifier_sender, my_receiver): (UnboundedSender<NotifierMessage>, UnboundedReceiver<NotifierMessage>) = mpsc::unbounded_channel();
+
+    Arc::clone(&module).start(supervisor).await;
+
+    // subscribe only once the module can take messages, until then they go to other processes
+    comm_infos.write().expect("failed acquiring lock").push(NotifierCommInfo {
+        name: module.name().to_string(),
+        topics: module.topics().iter().map(|topic| topic.to_string()).collect(),
+        broadcast_channel: notifier_sender
+    });
+
+    let listener = Arc::new(Mutex::new(my_receiver));
+    let looped_module = Arc::clone(&module);
+    supervisor.supervise(module.name(), move || {
+        tokio::spawn(module_loop(Arc::clone(&looped_module), Arc::clone(&listener)))
+    });
+    running.lock().await.push(module);
+}
+
+// unsubscribed first, so new messages for the module go to the process that leads next
+async fn stop_module(supervisor: &Supervisor, comm_infos: &RwLock<Vec<NotifierCommInfo>>, module: Arc<dyn Module>, running: &Mutex<Vec<Arc<dyn Module>>>) {
+    comm_infos.write().expect("failed acquiring lock").retain(|comm_info| comm_info.name != module.name());
+    supervisor.stop(module.name());
+    running.lock().await.retain(|other| other.name() != module.name());
+    Arc::clone(&module).stop().await;
+}
+
+impl RunningModules {
+    pub async fn stop(self) {
+        for module in self.modules.lock().await.drain(..) {
+            println!("stopping module {}", module.name());
+            module.stop().await;
+        }
+    }
+}
+
+async fn module_loop(module: Arc<dyn Module>, listener: Arc<Mutex<UnboundedReceiver<NotifierMessage>>>) {
+    loop {
+        let message = match listener.lock().await.recv().await {
+            Some(message) => message,
+            None => break
+        };
+        Arc::clone(&module).handle(message).await;
+    }
+}
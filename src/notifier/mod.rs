@@ -0,0 +1,411 @@
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex, OnceLock, RwLock}, time::Duration};
+
+use tokio::sync::{mpsc::{self, UnboundedReceiver, UnboundedSender}, oneshot};
+use uuid::Uuid;
+
+use crate::database::{event_log::EventLogEntry, DbConnection};
+use crate::deployer::DeployerCommand;
+use crate::events::DomainEvent;
+use crate::flag_receiver::FlagReceiverCommand;
+use crate::timer::TimerCommand;
+use crate::webhook::WebhookCommand;
+
+pub mod transport;
+
+use transport::{Transport, WirePayload};
+
+// every message travelling through the notifier, one variant per module that can receive it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum NotifierMessage {
+    Deployer(DeployerCommand),
+    FlagReceiver(FlagReceiverCommand),
+    Timer(TimerCommand),
+    Webhook(WebhookCommand),
+    Event(DomainEvent)
+}
+
+impl NotifierMessage {
+    // modules subscribe to topics, a message is delivered to every module subscribed to its topic
+    pub fn topic(&self) -> &'static str {
+        match self {
+            NotifierMessage::Deployer(_) => "deployer",
+            NotifierMessage::FlagReceiver(_) => "flag_receiver",
+            NotifierMessage::Timer(_) => "timer",
+            NotifierMessage::Webhook(_) => "webhook",
+            NotifierMessage::Event(event) => event.topic()
+        }
+    }
+
+    // broadcast messages reach every process, everything else is handled by a single module
+    pub fn is_broadcast(&self) -> bool {
+        matches!(self,
+            NotifierMessage::Event(_)
+            | NotifierMessage::FlagReceiver(FlagReceiverCommand::FlagInfo { .. })
+            | NotifierMessage::FlagReceiver(FlagReceiverCommand::Cleanup { .. })
+        )
+    }
+
+    // copy that is safe to write to the event log, real flags never leave the modules
+    pub fn redacted(&self) -> NotifierMessage {
+        match self {
+            NotifierMessage::FlagReceiver(FlagReceiverCommand::FlagInfo { challenge_name, rules }) => FlagReceiverCommand::FlagInfo {
+                challenge_name: challenge_name.clone(),
+                rules: rules.redacted()
+            }.into(),
+            NotifierMessage::FlagReceiver(FlagReceiverCommand::FlagSubmit { challenge, submit_by, ip, reply, .. }) => FlagReceiverCommand::FlagSubmit {
+                flag: "<redacted>".to_string(),
+                challenge: challenge.clone(),
+                submit_by: submit_by.clone(),
+                ip: ip.clone(),
+                reply: reply.clone()
+            }.into(),
+            other => other.clone()
+        }
+    }
+}
+
+impl From<DeployerCommand> for NotifierMessage {
+    fn from(cmd: DeployerCommand) -> Self {
+        NotifierMessage::Deployer(cmd)
+    }
+}
+
+impl From<FlagReceiverCommand> for NotifierMessage {
+    fn from(cmd: FlagReceiverCommand) -> Self {
+        NotifierMessage::FlagReceiver(cmd)
+    }
+}
+
+impl From<TimerCommand> for NotifierMessage {
+    fn from(cmd: TimerCommand) -> Self {
+        NotifierMessage::Timer(cmd)
+    }
+}
+
+impl From<WebhookCommand> for NotifierMessage {
+    fn from(cmd: WebhookCommand) -> Self {
+        NotifierMessage::Webhook(cmd)
+    }
+}
+
+impl From<DomainEvent> for NotifierMessage {
+    fn from(event: DomainEvent) -> Self {
+        NotifierMessage::Event(event)
+    }
+}
+
+// reply half of a request, handed to the target module inside the command.
+// fire-and-forget commands carry an empty responder, answering it is a no-op.
+// when the command crossed to another process the answer travels back through the transport.
+pub struct Responder<T> {
+    // identifies the request across processes
+    correlation_id: Option<String>,
+    channel: Arc<Mutex<Option<oneshot::Sender<T>>>>,
+    remote: bool
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    // the module did not answer in time
+    Timeout,
+    // the module dropped the command without answering
+    NoReply
+}
+
+type PendingReply = Box<dyn FnOnce(&str) + Send>;
+
+// requests of this process that may be answered by another one, keyed by correlation id
+fn pending_replies() -> &'static Mutex<HashMap<String, PendingReply>> {
+    static PENDING_REPLIES: OnceLock<Mutex<HashMap<String, PendingReply>>> = OnceLock::new();
+    PENDING_REPLIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// set by the running notifier, answers to remote requests are handed to it
+static REMOTE_REPLIES: OnceLock<UnboundedSender<WirePayload>> = OnceLock::new();
+
+impl<T> Responder<T> {
+    pub fn new() -> (Self, oneshot::Receiver<T>) {
+        let (sender, receiver) = oneshot::channel();
+        (Responder {
+            correlation_id: Some(Uuid::new_v4().to_string()),
+            channel: Arc::new(Mutex::new(Some(sender))),
+            remote: false
+        }, receiver)
+    }
+
+    pub fn none() -> Self {
+        Responder { correlation_id: None, channel: Arc::new(Mutex::new(None)), remote: false }
+    }
+}
+
+impl<T: serde::Serialize> Responder<T> {
+    pub fn respond(&self, value: T) {
+        if self.remote {
+            if let (Some(correlation_id), Some(route)) = (&self.correlation_id, REMOTE_REPLIES.get()) {
+                let _ = route.send(WirePayload::Reply {
+                    correlation_id: correlation_id.clone(),
+                    value: serde_json::to_string(&value).unwrap_or_default()
+                });
+            }
+            return;
+        }
+
+        let sender = self.channel.lock().expect("failed acquiring lock").take();
+        if let Some(sender) = sender {
+            // the requester may have timed out already, nobody to tell then
+            let _ = sender.send(value);
+        }
+    }
+}
+
+impl<T> Clone for Responder<T> {
+    fn clone(&self) -> Self {
+        Responder {
+            correlation_id: self.correlation_id.clone(),
+            channel: Arc::clone(&self.channel),
+            remote: self.remote
+        }
+    }
+}
+
+impl<T> fmt::Debug for Responder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Responder")
+    }
+}
+
+// only the correlation id leaves the process, the reply channel stays with the requester
+impl<T> serde::Serialize for Responder<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.correlation_id.serialize(serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Responder<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let correlation_id: Option<String> = Option::deserialize(deserializer)?;
+        Ok(Responder {
+            remote: correlation_id.is_some(),
+            correlation_id,
+            channel: Arc::new(Mutex::new(None))
+        })
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Timeout => f.write_str("module did not answer in time"),
+            RequestError::NoReply => f.write_str("module did not answer")
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NotifierCommInfo {
+    // pub id: u128,
+    pub name: String,
+    pub topics: Vec<String>,
+    pub broadcast_channel: UnboundedSender<NotifierMessage>,
+}
+
+// a message on its way through the notifier, tagged with whoever sent it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Envelope {
+    pub source: String,
+    pub message: NotifierMessage
+}
+
+// the sending half of the master channel, every module gets its own so the event log knows who said what
+#[derive(Clone)]
+pub struct NotifierSender {
+    source: String,
+    master: UnboundedSender<Envelope>
+}
+
+impl NotifierSender {
+    pub fn new(source: &str, master: UnboundedSender<Envelope>) -> Self {
+        NotifierSender { source: source.to_string(), master }
+    }
+
+    pub fn send<T: Into<NotifierMessage>>(&self, message: T) {
+        let envelope = Envelope { source: self.source.clone(), message: message.into() };
+        if self.master.send(envelope).is_err() {
+            println!("{} failed to reach the notifier", self.source);
+        }
+    }
+}
+
+// handle the web interface uses to reach the modules
+#[derive(Clone)]
+pub struct NotifierComms {
+    pub sender: NotifierSender
+}
+
+pub struct Notifier {
+    pub listen_master: UnboundedReceiver<Envelope>,
+    // modules that only run on the leader subscribe once it gets elected
+    pub comm_infos: Arc<RwLock<Vec<NotifierCommInfo>>>,
+    pub db_conn: DbConnection,
+    pub transport: Arc<dyn Transport>
+}
+
+impl NotifierComms {
+    pub fn notify<T: Into<NotifierMessage>>(&self, message: T) {
+        self.sender.send(message);
+    }
+
+    // send a command built around a fresh responder and wait for the module's answer
+    pub async fn request<T, M, F>(&self, build_message: F, timeout: Duration) -> Result<T, RequestError>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+        M: Into<NotifierMessage>,
+        F: FnOnce(Responder<T>) -> M
+    {
+        let (responder, receiver) = Responder::new();
+        let correlation_id = responder.correlation_id.clone().unwrap_or_default();
+
+        // in case the module lives in another process
+        let local_responder = responder.clone();
+        pending_replies().lock().expect("failed acquiring lock").insert(correlation_id.clone(), Box::new(move |value: &str| {
+            if let Ok(value) = serde_json::from_str::<T>(value) {
+                local_responder.respond(value);
+            }
+        }));
+
+        self.notify(build_message(responder));
+
+        let result = match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(RequestError::NoReply),
+            Err(_) => Err(RequestError::Timeout)
+        };
+
+        pending_replies().lock().expect("failed acquiring lock").remove(&correlation_id);
+        result
+    }
+}
+
+// main functions
+impl Notifier {
+    pub async fn run(mut self) {
+        let (incoming_sender, mut incoming): (UnboundedSender<WirePayload>, UnboundedReceiver<WirePayload>) = mpsc::unbounded_channel();
+        self.transport.listen(incoming_sender).await;
+
+        let (reply_sender, mut replies): (UnboundedSender<WirePayload>, UnboundedReceiver<WirePayload>) = mpsc::unbounded_channel();
+        if REMOTE_REPLIES.set(reply_sender).is_err() {
+            println!("notifier is already running, remote replies go to the first one");
+        }
+
+        loop {
+            tokio::select! {
+                envelope = self.listen_master.recv() => match envelope {
+                    Some(envelope) => self.route_local(envelope).await,
+                    None => break
+                },
+                Some(payload) = incoming.recv() => self.route_remote(payload),
+                Some(reply) = replies.recv() => self.transport.broadcast(reply).await
+            }
+        }
+    }
+
+    async fn route_local(&self, envelope: Envelope) {
+        println!("sending signal from {} to {}", envelope.source, envelope.message.topic());
+        self.record(&envelope);
+
+        let delivered = self.deliver(&envelope.message);
+
+        if envelope.message.is_broadcast() || !delivered {
+            // an event nobody listens to is fine, a command nobody handles is a bug
+            if !delivered && !self.transport.is_distributed() && !envelope.message.is_broadcast() {
+                println!("notifier failure, nobody is subscribed to {}", envelope.message.topic());
+            }
+            self.transport.broadcast(WirePayload::Message(envelope)).await;
+        }
+    }
+
+    // the process the message came from already recorded it
+    fn route_remote(&self, payload: WirePayload) {
+        match payload {
+            WirePayload::Message(envelope) => {
+                println!("remote signal from {} to {}", envelope.source, envelope.message.topic());
+                self.deliver(&envelope.message);
+            },
+            WirePayload::Reply { correlation_id, value } => {
+                let pending = pending_replies().lock().expect("failed acquiring lock").remove(&correlation_id);
+                if let Some(pending) = pending {
+                    pending(&value);
+                }
+            },
+            // only this process missed something, the others don't need to hear about it
+            WirePayload::Gap => {
+                let envelope = Envelope { source: "notifier".to_string(), message: DomainEvent::MessagesLost.into() };
+                self.record(&envelope);
+                self.deliver(&envelope.message);
+            }
+        }
+    }
+
+    // append to the event log without holding up delivery
+    fn record(&self, envelope: &Envelope) {
+        let entry = EventLogEntry::new(
+            envelope.source.clone(),
+            envelope.message.topic().to_string(),
+            serde_json::to_string(&envelope.message.redacted()).unwrap_or_default()
+        );
+        let db_conn = self.db_conn.clone();
+        tokio::spawn(async move {
+            db_conn.append_event_log(entry).await;
+        });
+    }
+
+    // hand the message to every local subscriber, returns whether there was any
+    fn deliver(&self, message: &NotifierMessage) -> bool {
+        let topic = message.topic();
+        let comm_infos = self.comm_infos.read().expect("failed acquiring lock");
+        let subscribers: Vec<&NotifierCommInfo> = comm_infos.iter()
+            .filter(|comm| comm.topics.iter().any(|subscribed| subscribed == topic))
+            .collect();
+
+        for comm_info in &subscribers {
+            if comm_info.broadcast_channel.send(message.clone()).is_err() {
+                println!("notifier failed to broadcast to {}, module is gone", comm_info.name);
+            }
+        }
+
+        !subscribers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flag_receiver::rules::FlagRules;
+
+    #[test]
+    fn redacted_flag_info_hides_the_rules() {
+        let rules: FlagRules = serde_json::from_str(r#"{"flags": ["coslivectf{a}"]}"#).unwrap();
+        let message: NotifierMessage = FlagReceiverCommand::FlagInfo { challenge_name: "pwn1".to_string(), rules }.into();
+        let logged = serde_json::to_string(&message.redacted()).unwrap();
+
+        assert!(!logged.contains("coslivectf{a}"));
+        assert!(logged.contains("pwn1"));
+    }
+
+    #[test]
+    fn redacted_flag_submit_hides_the_flag() {
+        let message: NotifierMessage = FlagReceiverCommand::FlagSubmit {
+            flag: "coslivectf{guess}".to_string(),
+            challenge: Some("pwn1".to_string()),
+            submit_by: "alice".to_string(),
+            ip: "10.0.0.1".to_string(),
+            reply: Responder::none()
+        }.into();
+        let logged = serde_json::to_string(&message.redacted()).unwrap();
+
+        assert!(!logged.contains("coslivectf{guess}"));
+        assert!(logged.contains("<redacted>"));
+        assert!(logged.contains("alice"));
+        assert!(logged.contains("pwn1"));
+    }
+}
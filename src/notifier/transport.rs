@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use sqlx::postgres::{PgConnection, PgListener};
+use tokio::sync::{mpsc::UnboundedSender, Mutex};
+use uuid::Uuid;
+
+use crate::database::{cluster::db_connection_alive, DbConnection};
+use crate::notifier::Envelope;
+
+const NOTIFY_CHANNEL: &str = "livectf_notifier";
+// any constant works as long as every livectf process uses the same one
+const LEADER_LOCK_KEY: i64 = 0x6c697665637466;
+const LEADER_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// what travels between livectf processes
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum WirePayload {
+    Message(Envelope),
+    // answer to a request made by another process, `value` is the JSON of the reply
+    Reply { correlation_id: String, value: String },
+    // never sent, the listener hands it to its own notifier after messages of other processes got lost
+    Gap
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WireMessage {
+    origin: String,
+    payload: WirePayload
+}
+
+// How notifiers of different livectf processes reach each other. Modules never see this,
+// the notifier decides what has to leave the process.
+pub trait Transport: Send + Sync + 'static {
+    // hand the payload to every other process
+    fn broadcast(&self, payload: WirePayload) -> BoxFuture<'_, ()>;
+
+    // start pushing what the other processes broadcast into `incoming`, and a Gap when some of it was lost
+    fn listen(&self, incoming: UnboundedSender<WirePayload>) -> BoxFuture<'_, ()>;
+
+    // resolves once this process is the leader, the one running the timer and the deployer
+    fn acquire_leadership(&self) -> BoxFuture<'_, ()>;
+
+    // resolves once this process stopped being the leader, another one may have taken over already
+    fn leadership_lost(&self) -> BoxFuture<'_, ()>;
+
+    fn is_distributed(&self) -> bool;
+}
+
+// a single livectf process, nothing to share and always the leader
+pub struct LocalTransport;
+
+impl Transport for LocalTransport {
+    fn broadcast(&self, _payload: WirePayload) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn listen(&self, _incoming: UnboundedSender<WirePayload>) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn acquire_leadership(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn leadership_lost(&self) -> BoxFuture<'_, ()> {
+        Box::pin(futures_util::future::pending())
+    }
+
+    fn is_distributed(&self) -> bool {
+        false
+    }
+}
+
+// Several processes sharing one database: messages go through LISTEN/NOTIFY,
+// the leader is whoever holds the advisory lock.
+// NOTIFY payloads are capped at 8000 bytes, our messages are far below that.
+pub struct PostgresTransport {
+    process_id: String,
+    db_conn: DbConnection,
+    // the advisory lock lives as long as this connection
+    leader_conn: Mutex<Option<PgConnection>>
+}
+
+impl PostgresTransport {
+    pub fn new(db_conn: DbConnection) -> Self {
+        PostgresTransport {
+            process_id: Uuid::new_v4().to_string(),
+            db_conn,
+            leader_conn: Mutex::new(None)
+        }
+    }
+}
+
+impl Transport for PostgresTransport {
+    fn broadcast(&self, payload: WirePayload) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            let message = WireMessage { origin: self.process_id.clone(), payload };
+            let message = match serde_json::to_string(&message) {
+                Ok(message) => message,
+                Err(err) => {
+                    println!("failed encoding message for other processes: {}", err);
+                    return;
+                }
+            };
+
+            if !self.db_conn.notify_channel(NOTIFY_CHANNEL, &message).await {
+                println!("failed broadcasting message to other processes");
+            }
+        })
+    }
+
+    fn listen(&self, incoming: UnboundedSender<WirePayload>) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            let mut listener = match self.db_conn.listen_channel(NOTIFY_CHANNEL).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    println!("failed listening for other processes, running alone: {}", err);
+                    return;
+                }
+            };
+
+            let process_id = self.process_id.clone();
+            let db_conn = self.db_conn.clone();
+            tokio::spawn(async move {
+                loop {
+                    // None is a lost connection, whatever was sent until we listen again is gone
+                    let notification = match listener.try_recv().await {
+                        Ok(Some(notification)) => notification,
+                        Ok(None) | Err(_) => {
+                            println!("lost connection to other processes, reconnecting");
+                            listener = reconnect(&db_conn).await;
+                            if incoming.send(WirePayload::Gap).is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+
+                    let message: WireMessage = match serde_json::from_str(notification.payload()) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            println!("ignoring malformed message from another process: {}", err);
+                            continue;
+                        }
+                    };
+
+                    // NOTIFY echoes back to the sender too
+                    if message.origin == process_id {
+                        continue;
+                    }
+
+                    if incoming.send(message.payload).is_err() {
+                        break;
+                    }
+                }
+            });
+        })
+    }
+
+    fn acquire_leadership(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            loop {
+                if let Some(conn) = self.db_conn.try_advisory_lock(LEADER_LOCK_KEY).await {
+                    println!("process {} is now the leader", self.process_id);
+                    *self.leader_conn.lock().await = Some(conn);
+                    return;
+                }
+                tokio::time::sleep(LEADER_RETRY_DELAY).await;
+            }
+        })
+    }
+
+    // the lock goes with the connection, so a connection that stops answering means the lock is gone
+    fn leadership_lost(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            loop {
+                tokio::time::sleep(LEADER_RETRY_DELAY).await;
+
+                let mut leader_conn = self.leader_conn.lock().await;
+                let alive = match leader_conn.as_mut() {
+                    Some(conn) => db_connection_alive(conn).await,
+                    None => false
+                };
+
+                if !alive {
+                    println!("process {} lost the leader lock", self.process_id);
+                    *leader_conn = None;
+                    return;
+                }
+            }
+        })
+    }
+
+    fn is_distributed(&self) -> bool {
+        true
+    }
+}
+
+async fn reconnect(db_conn: &DbConnection) -> PgListener {
+    loop {
+        match db_conn.listen_channel(NOTIFY_CHANNEL).await {
+            Ok(listener) => return listener,
+            Err(err) => {
+                println!("failed listening for other processes, retrying: {}", err);
+                tokio::time::sleep(LEADER_RETRY_DELAY).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+
+use futures_util::future::BoxFuture;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::database::{settings::TEAM_EVENT_SETTING, DbConnection};
+use crate::module::Module;
+use crate::notifier::NotifierMessage;
+use crate::utils::{self, ScoreBoardTeam, ScoreBoardUser};
+
+// events after which the scoreboard has to be computed again
+pub const SCOREBOARD_TOPICS: [&str; 8] = ["flag_accepted", "stage_solved", "hint_unlocked", "challenge_released", "challenge_retired", "user_registered", "score_changed", "messages_lost"];
+
+pub struct Scoreboard {
+    pub users: Vec<ScoreBoardUser>,
+    pub teams: Vec<ScoreBoardTeam>,
+    // teams are ranked instead of players, changing the setting sends score_changed
+    pub team_event: bool
+}
+
+impl Scoreboard {
+    pub fn user_score(&self, username: &str) -> u64 {
+        self.users.iter().find(|user| user.username == username).map(|user| user.score).unwrap_or_default()
+    }
+}
+
+// The scoreboard is computed by the first request after a change and shared by every request until the next one.
+// Runs on every livectf process, events are broadcast so each process drops its own copy.
+pub(crate) struct ScoreboardCache {
+    db_conn: DbConnection,
+    cached: RwLock<Option<Arc<Scoreboard>>>,
+    // only one request computes it, the others wait and take the result
+    refresh: Mutex<()>,
+    // bumped by every change, a scoreboard computed before the last bump is handed out but never kept
+    generation: AtomicU64
+}
+
+impl ScoreboardCache {
+    pub(crate) fn new(db_conn: DbConnection) -> Self {
+        ScoreboardCache {
+            db_conn,
+            cached: RwLock::new(None),
+            refresh: Mutex::new(()),
+            generation: AtomicU64::new(0)
+        }
+    }
+
+    pub async fn get(&self) -> Arc<Scoreboard> {
+        if let Some(scoreboard) = self.cached.read().await.as_ref() {
+            return Arc::clone(scoreboard);
+        }
+
+        let _refresh = self.refresh.lock().await;
+        if let Some(scoreboard) = self.cached.read().await.as_ref() {
+            return Arc::clone(scoreboard);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let users = utils::get_scoreboard(&self.db_conn).await;
+        let team_event = self.db_conn.get_settings().await.get(TEAM_EVENT_SETTING) == 1;
+        let scoreboard = Arc::new(Scoreboard { teams: utils::get_team_scoreboard(&users), users, team_event });
+
+        let mut cached = self.cached.write().await;
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(Arc::clone(&scoreboard));
+        }
+        scoreboard
+    }
+
+    // for changes this process made and has to show right away, other processes learn about them from the event
+    pub async fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.cached.write().await = None;
+    }
+}
+
+impl Module for ScoreboardCache {
+    fn name(&self) -> &'static str {
+        "scoreboard_cache"
+    }
+
+    fn topics(&self) -> Vec<&'static str> {
+        SCOREBOARD_TOPICS.to_vec()
+    }
+
+    fn handle(self: Arc<Self>, _message: NotifierMessage) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            self.invalidate().await;
+        })
+    }
+}
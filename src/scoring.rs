@@ -0,0 +1,287 @@
+// How many points a challenge is worth as players solve it. Every challenge uses the event's
+// scoring unless it ships its own (chall/scoring.json or sheep center), see database::challenge::update_challenge_score.
+pub trait ScoringStrategy {
+    // points every solver gets once solve_count players solved the challenge
+    fn points(&self, solve_count: i32) -> i32;
+
+    fn validate(&self) -> Result<(), String>;
+}
+
+// same points no matter how many solve it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StaticScoring {
+    pub points: i32
+}
+
+// CTFd's dynamic value: quadratic decay from initial, reaching minimum after decay solves
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DynamicScoring {
+    pub initial: i32,
+    pub minimum: i32,
+    pub decay: i32
+}
+
+// loses decay points with every solve
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LinearScoring {
+    pub initial: i32,
+    pub minimum: i32,
+    pub decay: i32
+}
+
+// drops fast for the first solves and slower later, reaching minimum after decay solves
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LogarithmicScoring {
+    pub initial: i32,
+    pub minimum: i32,
+    pub decay: i32
+}
+
+// what gets stored and sent by sheep center, e.g. {"kind": "dynamic", "initial": 500, "minimum": 50, "decay": 30}
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScoringConfig {
+    Static(StaticScoring),
+    Dynamic(DynamicScoring),
+    Linear(LinearScoring),
+    Logarithmic(LogarithmicScoring)
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig::Dynamic(DynamicScoring { initial: 500, minimum: 50, decay: 300 })
+    }
+}
+
+impl ScoringConfig {
+    pub fn strategy(&self) -> &dyn ScoringStrategy {
+        match self {
+            ScoringConfig::Static(strategy) => strategy,
+            ScoringConfig::Dynamic(strategy) => strategy,
+            ScoringConfig::Linear(strategy) => strategy,
+            ScoringConfig::Logarithmic(strategy) => strategy
+        }
+    }
+}
+
+impl ScoringStrategy for StaticScoring {
+    fn points(&self, _solve_count: i32) -> i32 {
+        self.points
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.points < 0 {
+            return Err("points can't be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ScoringStrategy for DynamicScoring {
+    fn points(&self, solve_count: i32) -> i32 {
+        let solves = f64::from(solves_after_first(solve_count));
+        let slope = f64::from(self.minimum - self.initial) / f64::from(self.decay).powi(2);
+        clamp(f64::from(self.initial) + slope * solves.powi(2), self.minimum)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_decay(self.initial, self.minimum, self.decay)
+    }
+}
+
+impl ScoringStrategy for LinearScoring {
+    fn points(&self, solve_count: i32) -> i32 {
+        let solves = f64::from(solves_after_first(solve_count));
+        clamp(f64::from(self.initial) - f64::from(self.decay) * solves, self.minimum)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_decay(self.initial, self.minimum, self.decay)
+    }
+}
+
+impl ScoringStrategy for LogarithmicScoring {
+    fn points(&self, solve_count: i32) -> i32 {
+        let solves = f64::from(solves_after_first(solve_count));
+        let progress = (1.0 + solves).ln() / (1.0 + f64::from(self.decay)).ln();
+        clamp(f64::from(self.initial) - f64::from(self.initial - self.minimum) * progress, self.minimum)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_decay(self.initial, self.minimum, self.decay)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BonusKind {
+    #[default]
+    Points,
+    // percent of what the challenge is worth right after the solve
+    Percent
+}
+
+// extra points for the first solvers of every challenge, bonuses[0] is the first blood's.
+// the bonus is fixed when the solve is recorded, later decay doesn't change it
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BloodBonus {
+    pub kind: BonusKind,
+    pub bonuses: Vec<i32>
+}
+
+impl BloodBonus {
+    // position 1 is the first solver of a challenge worth points
+    pub fn bonus(&self, position: i32, points: i32) -> i32 {
+        let bonus = usize::try_from(position - 1).ok()
+            .and_then(|index| self.bonuses.get(index))
+            .copied()
+            .unwrap_or_default();
+
+        match self.kind {
+            BonusKind::Points => bonus,
+            BonusKind::Percent => points * bonus / 100
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bonuses.iter().any(|bonus| *bonus < 0) {
+            return Err("bonuses can't be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+// shown next to the first, second and third solver of a challenge
+pub const BLOOD_BADGES: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+pub fn blood_badge(position: i32) -> Option<&'static str> {
+    usize::try_from(position - 1).ok().and_then(|index| BLOOD_BADGES.get(index)).copied()
+}
+
+// the first solver gets the initial value, decay starts with the second
+fn solves_after_first(solve_count: i32) -> i32 {
+    (solve_count - 1).max(0)
+}
+
+fn clamp(points: f64, minimum: i32) -> i32 {
+    (points.ceil() as i32).max(minimum)
+}
+
+fn validate_decay(initial: i32, minimum: i32, decay: i32) -> Result<(), String> {
+    if minimum < 0 || initial < minimum {
+        return Err("initial must be at least minimum, and minimum can't be negative".to_string());
+    }
+    if decay <= 0 {
+        return Err("decay must be positive".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_points_never_change() {
+        let scoring = StaticScoring { points: 300 };
+
+        assert_eq!(scoring.points(0), 300);
+        assert_eq!(scoring.points(1000), 300);
+    }
+
+    #[test]
+    fn dynamic_points_decay_quadratically() {
+        let scoring = DynamicScoring { initial: 500, minimum: 50, decay: 30 };
+
+        assert_eq!(scoring.points(0), 500);
+        assert_eq!(scoring.points(1), 500);
+        assert_eq!(scoring.points(16), 388);
+        assert_eq!(scoring.points(31), 50);
+        assert_eq!(scoring.points(100), 50);
+    }
+
+    #[test]
+    fn linear_points_lose_decay_per_solve() {
+        let scoring = LinearScoring { initial: 500, minimum: 100, decay: 50 };
+
+        assert_eq!(scoring.points(1), 500);
+        assert_eq!(scoring.points(3), 400);
+        assert_eq!(scoring.points(20), 100);
+    }
+
+    #[test]
+    fn logarithmic_points_reach_minimum_after_decay_solves() {
+        let scoring = LogarithmicScoring { initial: 500, minimum: 50, decay: 30 };
+
+        assert_eq!(scoring.points(1), 500);
+        assert_eq!(scoring.points(4), 319);
+        assert_eq!(scoring.points(31), 50);
+        assert_eq!(scoring.points(500), 50);
+    }
+
+    #[test]
+    fn points_never_go_up_with_more_solves() {
+        let configs = [
+            ScoringConfig::default(),
+            ScoringConfig::Linear(LinearScoring { initial: 500, minimum: 100, decay: 7 }),
+            ScoringConfig::Logarithmic(LogarithmicScoring { initial: 500, minimum: 50, decay: 30 })
+        ];
+
+        for config in configs {
+            let points: Vec<i32> = (1..200).map(|solves| config.strategy().points(solves)).collect();
+            assert!(points.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        assert!(StaticScoring { points: -1 }.validate().is_err());
+        assert!(DynamicScoring { initial: 50, minimum: 100, decay: 10 }.validate().is_err());
+        assert!(LinearScoring { initial: 100, minimum: -1, decay: 10 }.validate().is_err());
+        assert!(LogarithmicScoring { initial: 100, minimum: 10, decay: 0 }.validate().is_err());
+        assert!(ScoringConfig::default().strategy().validate().is_ok());
+    }
+
+    #[test]
+    fn configs_are_tagged_by_kind() {
+        let config: ScoringConfig = serde_json::from_str(r#"{"kind": "linear", "initial": 500, "minimum": 100, "decay": 50}"#).unwrap();
+
+        assert_eq!(config, ScoringConfig::Linear(LinearScoring { initial: 500, minimum: 100, decay: 50 }));
+        assert!(serde_json::from_str::<ScoringConfig>(r#"{"kind": "exponential"}"#).is_err());
+    }
+
+    #[test]
+    fn blood_bonuses_go_to_the_first_solvers() {
+        let bonus = BloodBonus { kind: BonusKind::Points, bonuses: vec![30, 20, 10] };
+
+        assert_eq!(bonus.bonus(1, 500), 30);
+        assert_eq!(bonus.bonus(3, 500), 10);
+        assert_eq!(bonus.bonus(4, 500), 0);
+        assert_eq!(bonus.bonus(0, 500), 0);
+    }
+
+    #[test]
+    fn percent_bonuses_follow_the_points() {
+        let bonus = BloodBonus { kind: BonusKind::Percent, bonuses: vec![10, 5] };
+
+        assert_eq!(bonus.bonus(1, 500), 50);
+        assert_eq!(bonus.bonus(2, 499), 24);
+        assert_eq!(bonus.bonus(3, 500), 0);
+        assert_eq!(BloodBonus::default().bonus(1, 500), 0);
+    }
+
+    #[test]
+    fn negative_bonuses_are_rejected() {
+        assert!(BloodBonus { kind: BonusKind::Points, bonuses: vec![10, -1] }.validate().is_err());
+        assert!(BloodBonus::default().validate().is_ok());
+    }
+
+    #[test]
+    fn badges_for_the_podium_only() {
+        assert_eq!(blood_badge(1), Some("🥇"));
+        assert_eq!(blood_badge(3), Some("🥉"));
+        assert_eq!(blood_badge(4), None);
+        assert_eq!(blood_badge(0), None);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::database::DbConnection;
+use crate::module::ModuleRegistry;
+
+// Site-specific modules (announcers, anti-cheat analyzers, ...) are registered here,
+// the core modules and the notifier never need to know about them.
+// use register_leader instead for modules that must only run once across all livectf processes.
+// e.g. registry.register(Arc::new(discord::DiscordAnnouncer::new(registry.sender("discord"), db_conn.clone())));
+pub(crate) fn register(_registry: &mut ModuleRegistry, _db_conn: DbConnection) {
+}
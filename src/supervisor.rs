@@ -0,0 +1,127 @@
+use std::{any::Any, collections::BTreeMap, sync::{Arc, RwLock}, time::Duration};
+
+use tokio::{sync::Notify, task::JoinHandle};
+
+// give a crashing module a moment before bringing it back so a poison message can't spin the cpu
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, PartialEq)]
+pub enum ModuleStatus {
+    Running,
+    Restarting,
+    Stopped
+}
+
+#[derive(Clone)]
+pub struct ModuleHealth {
+    pub name: String,
+    pub status: ModuleStatus,
+    pub restarts: u32,
+    pub running_since: i64,
+    pub last_crash_at: Option<i64>,
+    pub last_crash: Option<String>
+}
+
+// owns the lifecycle of every module task: restarts the ones that panic and keeps track of their health.
+// modules keep their state outside of the task (behind an Arc), so a restarted task picks up where the dead one left off.
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    health: Arc<RwLock<BTreeMap<String, ModuleHealth>>>,
+    // wakes up the task of a module that has to stop, see stop
+    stops: Arc<RwLock<BTreeMap<String, Arc<Notify>>>>
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Supervisor {
+            health: Arc::new(RwLock::new(BTreeMap::new())),
+            stops: Arc::new(RwLock::new(BTreeMap::new()))
+        }
+    }
+
+    // `start` is called once now and again after every crash, it must spawn a fresh task each time
+    pub fn supervise<F>(&self, name: &str, start: F)
+    where
+        F: Fn() -> JoinHandle<()> + Send + 'static
+    {
+        let name = name.to_string();
+        let health = Arc::clone(&self.health);
+        let stop = Arc::new(Notify::new());
+        self.stops.write().expect("failed acquiring lock").insert(name.clone(), Arc::clone(&stop));
+
+        health.write().expect("failed acquiring lock").insert(name.clone(), ModuleHealth {
+            name: name.clone(),
+            status: ModuleStatus::Running,
+            restarts: 0,
+            running_since: chrono::offset::Utc::now().timestamp(),
+            last_crash_at: None,
+            last_crash: None
+        });
+
+        tokio::spawn(async move {
+            loop {
+                let mut task = start();
+                let result = tokio::select! {
+                    result = &mut task => result,
+                    _ = stop.notified() => {
+                        task.abort();
+                        Ok(())
+                    }
+                };
+
+                match result {
+                    Err(err) if err.is_panic() => {
+                        let reason = panic_message(err.into_panic());
+                        println!("module {} crashed: {}, restarting", name, reason);
+
+                        if let Some(module) = health.write().expect("failed acquiring lock").get_mut(&name) {
+                            module.status = ModuleStatus::Restarting;
+                            module.restarts += 1;
+                            module.last_crash_at = Some(chrono::offset::Utc::now().timestamp());
+                            module.last_crash = Some(reason);
+                        }
+
+                        tokio::time::sleep(RESTART_DELAY).await;
+
+                        if let Some(module) = health.write().expect("failed acquiring lock").get_mut(&name) {
+                            module.status = ModuleStatus::Running;
+                            module.running_since = chrono::offset::Utc::now().timestamp();
+                        }
+                    },
+                    // the module returned on its own (its channel closed), got cancelled or stopped, nothing to restart
+                    _ => {
+                        println!("module {} stopped", name);
+
+                        if let Some(module) = health.write().expect("failed acquiring lock").get_mut(&name) {
+                            module.status = ModuleStatus::Stopped;
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    // aborts the task started by supervise under this name, it isn't restarted
+    pub fn stop(&self, name: &str) {
+        if let Some(stop) = self.stops.read().expect("failed acquiring lock").get(name) {
+            stop.notify_one();
+        }
+    }
+
+    pub fn health(&self) -> Vec<ModuleHealth> {
+        self.health.read().expect("failed acquiring lock").values().cloned().collect()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        return msg.to_string();
+    }
+
+    if let Some(msg) = payload.downcast_ref::<String>() {
+        return msg.clone();
+    }
+
+    "unknown panic".to_string()
+}
@@ -1,10 +1,29 @@
-use core::time;
-use std::{collections::{BinaryHeap, HashMap}, sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread::{sleep, spawn}, time::{SystemTime, UNIX_EPOCH}};
+use std::{collections::BinaryHeap, sync::{Arc, OnceLock}, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 // use uuid::Uuid;
+use futures_util::future::BoxFuture;
+use tokio::sync::Mutex;
 
-use crate::notifier::{craft_type_notify_message, Notifier, NotifierCommInfo};
+use crate::database::{challenge::ChallengeData, settings::PAUSED_AT_SETTING, DbConnection};
+use crate::deployer::DeployerCommand;
+use crate::module::Module;
+use crate::notifier::{NotifierMessage, NotifierSender, Responder};
+use crate::supervisor::Supervisor;
 
+pub mod rounds;
+
+pub const TIMER_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum TimerCommand {
+    Enqueue { challenge_name: String, start_time: i128, end_time: i128 },
+    DeployInfo { challenge_name: String, success: bool },
+    // both answer whether the event actually changed state
+    Pause { reply: Responder<bool> },
+    Resume { reply: Responder<bool> }
+}
+
+// (challenge name, when it fires)
 #[derive(PartialEq, Eq)]
 struct ScheduledChallenge(String, i128);
 
@@ -13,7 +32,9 @@ struct DeployedChallenge(String, i128);
 
 struct TimerQueue {
     scheduled_queue: BinaryHeap<ScheduledChallenge>,
-    deployed_queue: BinaryHeap<DeployedChallenge>
+    deployed_queue: BinaryHeap<DeployedChallenge>,
+    // while the event is paused nothing gets deployed or destroyed
+    paused_at: Option<i128>
 }
 
 impl Ord for ScheduledChallenge {
@@ -40,79 +61,133 @@ impl PartialOrd for DeployedChallenge {
     }
 }
 
-struct TimerCtx {
+pub(crate) struct TimerCtx {
     // main comm channel
-    sender: Sender<(String, Vec<u8>)>,
-    listener: Receiver<Vec<u8>>,
+    sender: NotifierSender,
+
+    db_conn: DbConnection,
+    timer_queue: Arc<Mutex<TimerQueue>>,
+    // set in start, stops the countdown when the leadership is lost
+    supervisor: OnceLock<Supervisor>
 }
 
-pub(crate) fn init(notifier: &mut Notifier, my_sender: Sender<(String, Vec<u8>)>) {
-    let (notifier_sender, my_receiver) : (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
-    
-    let ctx = TimerCtx {
-        sender: my_sender,
-        listener: my_receiver,
-    };
+impl TimerCtx {
+    pub(crate) fn new(my_sender: NotifierSender, db_conn: DbConnection) -> Self {
+        TimerCtx {
+            sender: my_sender,
+            db_conn,
+            timer_queue: Arc::new(Mutex::new(TimerQueue { scheduled_queue: BinaryHeap::new(), deployed_queue: BinaryHeap::new(), paused_at: None })),
+            supervisor: OnceLock::new()
+        }
+    }
+}
 
-    let comm_info = NotifierCommInfo {
-        // id: Uuid::new_v4().as_u128(),
-        name: "timer".to_string(),
-        broadcast_channel: notifier_sender
-    };
-    notifier.slaves.comm_infos.push(comm_info);
-    spawn(move || {
-        timer_loop(ctx)
-    });
-}
-
-fn timer_loop(mut ctx: TimerCtx) {
-    let timer_queue: Arc<Mutex<TimerQueue>> = Arc::new(Mutex::new(TimerQueue { scheduled_queue: BinaryHeap::new(), deployed_queue: BinaryHeap::new() }));
-    let timer_queue_clone = Arc::clone(&timer_queue);
-    let countdown_sender = ctx.sender.clone();
-    spawn(move || {
-        countdown(timer_queue_clone, countdown_sender)
-    });
-    loop {
-        let serialized_data = ctx.listener.recv().expect("timer channel communication exited");
-        let data = deserialize_data(&serialized_data);
-        match data.get("cmd").expect("missing cmd").as_str() {
+impl Module for TimerCtx {
+    fn name(&self) -> &'static str {
+        "timer"
+    }
+
+    fn topics(&self) -> Vec<&'static str> {
+        vec!["timer"]
+    }
 
-            "enqueue" => cmd_enqueue(&mut ctx, timer_queue.clone(), &data),
+    fn start<'a>(self: Arc<Self>, supervisor: &'a Supervisor) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let _ = self.supervisor.set(supervisor.clone());
+            restore_queue(&self).await;
 
-            "deploy_info" => cmd_deploy_info(&mut ctx, timer_queue.clone(), &data),
+            // the countdown is its own task so a crash in either one doesn't stop the other
+            supervisor.supervise("timer_countdown", move || {
+                tokio::spawn(countdown(Arc::clone(&self.timer_queue), self.sender.clone()))
+            });
+        })
+    }
 
-            _ => panic!("unknown cmd")
-        }
+    // the next leader schedules the challenges itself, what is queued here would fire twice if this process leads again
+    fn stop(self: Arc<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            if let Some(supervisor) = self.supervisor.get() {
+                supervisor.stop("timer_countdown");
+            }
+
+            let mut timer_queue = self.timer_queue.lock().await;
+            timer_queue.scheduled_queue.clear();
+            timer_queue.deployed_queue.clear();
+        })
+    }
+
+    fn handle(self: Arc<Self>, message: NotifierMessage) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let timer_queue = Arc::clone(&self.timer_queue);
+            let cmd = match message {
+                NotifierMessage::Timer(cmd) => cmd,
+                other => {
+                    println!("timer ignoring message on {}", other.topic());
+                    return;
+                }
+            };
+
+            match cmd {
+
+                TimerCommand::Enqueue { challenge_name, start_time, end_time } => cmd_enqueue(&self, timer_queue, challenge_name, start_time, end_time).await,
+
+                TimerCommand::DeployInfo { challenge_name, success } => cmd_deploy_info(&self, timer_queue, challenge_name, success).await,
+
+                TimerCommand::Pause { reply } => reply.respond(cmd_pause(&self, timer_queue).await),
+
+                TimerCommand::Resume { reply } => reply.respond(cmd_resume(&self, timer_queue).await)
+            }
+        })
     }
 }
 
-fn deserialize_data(serialized_data: &Vec<u8>) -> HashMap<&str, String> {
-    let data: HashMap<&str, String> = serde_json::from_slice(serialized_data.as_slice()).expect("deserialize failed!");
-    return data;
+// the queue only lives in memory, after a restart or a change of leader it comes back from the schedule in the database.
+// whatever should have fired in the meantime fires on the next tick
+async fn restore_queue(ctx: &TimerCtx) {
+    let paused_at = ctx.db_conn.get_settings().await.get(PAUSED_AT_SETTING);
+    let challenges = ctx.db_conn.get_pending_challenges().await;
+    let mut timer_queue = ctx.timer_queue.lock().await;
+
+    (timer_queue.scheduled_queue, timer_queue.deployed_queue) = restored_queues(&challenges, get_now_epoch());
+    timer_queue.paused_at = (paused_at > 0).then_some(i128::from(paused_at));
+    println!("restored {} scheduled and {} deployed challenges", timer_queue.scheduled_queue.len(), timer_queue.deployed_queue.len());
+}
+
+fn restored_queues(challenges: &[ChallengeData], now_epoch: i128) -> (BinaryHeap<ScheduledChallenge>, BinaryHeap<DeployedChallenge>) {
+    let mut scheduled_queue = BinaryHeap::new();
+    let mut deployed_queue = BinaryHeap::new();
+
+    for challenge in challenges.iter().filter(|challenge| challenge.is_pending(now_epoch as i64)) {
+        if !challenge.running {
+            scheduled_queue.push(ScheduledChallenge(challenge.challenge_name.clone(), i128::from(challenge.start_time)));
+        }
+        // challenges deployed by hand have no end
+        if challenge.end_time > 0 {
+            deployed_queue.push(DeployedChallenge(challenge.challenge_name.clone(), i128::from(challenge.end_time)));
+        }
+    }
+
+    (scheduled_queue, deployed_queue)
 }
 
-fn cmd_enqueue(_ctx: &mut TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>, data: &HashMap<&str, String>) {
-    let mut timer_queue = timer_queue_guard.lock().expect("failed acquiring lock");
-    let challenge_name = data.get("challenge_name").expect("missing challenge_name");
-    let start_time = i128::from_str_radix(
-        data.get("start_time").expect("missing start_time"),
-        10).expect("invalid start_time");
-    let end_time = i128::from_str_radix(
-                            data.get("end_time").expect("missing end_time"),
-                            10).expect("invalid end_time");
+async fn cmd_enqueue(_ctx: &TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>, challenge_name: String, start_time: i128, end_time: i128) {
+    let mut timer_queue = timer_queue_guard.lock().await;
     timer_queue.scheduled_queue.push(ScheduledChallenge(challenge_name.to_string(), start_time));
     timer_queue.deployed_queue.push(DeployedChallenge(challenge_name.to_string(), end_time));
 }
 
-fn countdown(timer_queue_guard: Arc<Mutex<TimerQueue>>, sender: Sender<(String, Vec<u8>)>) {
+async fn countdown(timer_queue_guard: Arc<Mutex<TimerQueue>>, sender: NotifierSender) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
 
     loop {
-        sleep(time::Duration::from_secs(1));
-        let mut timer_queue = timer_queue_guard.lock().expect("failed acquiring lock");
+        interval.tick().await;
+        let mut timer_queue = timer_queue_guard.lock().await;
 
-        let now_epoch = i128::try_from(
-            SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs()
-        ).expect("Cannot convert current epoch to i128");
+        if timer_queue.paused_at.is_some() {
+            continue;
+        }
+
+        let now_epoch = get_now_epoch();
 
         if timer_queue.scheduled_queue.len() != 0 {
 
@@ -120,15 +195,10 @@ fn countdown(timer_queue_guard: Arc<Mutex<TimerQueue>>, sender: Sender<(String,
 
             if now_epoch >= challenge_start_time {
 
-                let challenge_name = &timer_queue.scheduled_queue.peek().expect("failed peeking timer queue").0;
-                let target_module = String::from("deployer");
-                let data = craft_type_notify_message(&target_module, &["deploy", challenge_name]);
-                sender.send((target_module, data)).expect("deployer cannot send");
+                let challenge_name = timer_queue.scheduled_queue.peek().expect("failed peeking timer queue").0.to_string();
+                sender.send(DeployerCommand::Deploy { challenge_filename: challenge_name, reply: Responder::none() });
                 timer_queue.scheduled_queue.pop();
                 
-            } else if now_epoch < challenge_start_time {
-                // TODO:
-                // Make announce through telgram bot or discord bot or sth!!!!!
             }
         }
 
@@ -137,9 +207,8 @@ fn countdown(timer_queue_guard: Arc<Mutex<TimerQueue>>, sender: Sender<(String,
             let challenge_end_time = timer_queue.deployed_queue.peek().expect("failed peeking timer queue").1;
             if now_epoch >= challenge_end_time {
 
-                let target_module = String::from("deployer");
-                let data = craft_type_notify_message(&target_module, &["destroy", &timer_queue.deployed_queue.peek().expect("failed peeking timer queue").0]);
-                sender.send((target_module, data)).expect("deployer cannot send");
+                let challenge_name = timer_queue.deployed_queue.peek().expect("failed peeking timer queue").0.to_string();
+                sender.send(DeployerCommand::Destroy { challenge_filename: challenge_name, reply: Responder::none() });
                 timer_queue.deployed_queue.pop();
 
             }
@@ -147,12 +216,107 @@ fn countdown(timer_queue_guard: Arc<Mutex<TimerQueue>>, sender: Sender<(String,
     }
 }
 
-fn cmd_deploy_info(_ctx: &mut TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>, data: &HashMap<&str, String>) {
-    let mut timer_queue = timer_queue_guard.lock().expect("failed acquiring lock");
-    let challenge_name = data.get("challenge_name").expect("missing challenge_name");
-    let deploy_status = data.get("deploy_status").expect("missing deploy_status");
+async fn cmd_deploy_info(_ctx: &TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>, challenge_name: String, success: bool) {
+    let mut timer_queue = timer_queue_guard.lock().await;
+
+    if !success {
+        timer_queue.deployed_queue.retain(|deployed_challenge| deployed_challenge.0 != challenge_name);
+    }
+}
+
+async fn cmd_pause(ctx: &TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>) -> bool {
+    let mut timer_queue = timer_queue_guard.lock().await;
+
+    if timer_queue.paused_at.is_some() {
+        return false;
+    }
+
+    let paused_at = get_now_epoch();
+    timer_queue.paused_at = Some(paused_at);
+    ctx.db_conn.set_setting(PAUSED_AT_SETTING, &paused_at.to_string()).await;
+    println!("event paused");
+    true
+}
+
+async fn cmd_resume(ctx: &TimerCtx, timer_queue_guard: Arc<Mutex<TimerQueue>>) -> bool {
+    let mut timer_queue = timer_queue_guard.lock().await;
+
+    let paused_at = match timer_queue.paused_at.take() {
+        Some(paused_at) => paused_at,
+        None => return false
+    };
+    let paused_duration = get_now_epoch() - paused_at;
+
+    // schedules made before the pause are pushed back by however long we were paused, the ones made
+    // during the pause already fire when the admin wanted them to. the queue is rebuilt from the shifted schedules
+    ctx.db_conn.shift_pending_challenge_schedules(paused_at as i64, paused_duration as i64).await;
+    ctx.db_conn.set_setting(PAUSED_AT_SETTING, "0").await;
+
+    let challenges = ctx.db_conn.get_pending_challenges().await;
+    (timer_queue.scheduled_queue, timer_queue.deployed_queue) = restored_queues(&challenges, get_now_epoch());
+
+    println!("event resumed, schedule shifted by {}s", paused_duration);
+    true
+}
+
+fn get_now_epoch() -> i128 {
+    i128::from(SystemTime::now().duration_since(UNIX_EPOCH).expect("back to the future!!!").as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn challenge(name: &str, running: bool, start_time: i64, end_time: i64) -> ChallengeData {
+        ChallengeData {
+            id: 1,
+            challenge_name: name.to_string(),
+            score: 500,
+            category: String::new(),
+            solved_by: vec![],
+            running,
+            connection_string: String::new(),
+            start_time,
+            end_time
+        }
+    }
+
+    fn names<T>(queue: BinaryHeap<T>, name: impl Fn(&T) -> &str) -> Vec<String> {
+        let mut names: Vec<String> = queue.iter().map(|entry| name(entry).to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn restart_brings_back_pending_challenges() {
+        let (scheduled, deployed) = restored_queues(&[
+            challenge("upcoming", false, NOW + 60, NOW + 3600),
+            challenge("running", true, NOW - 60, NOW + 3600),
+            challenge("overdue", false, NOW - 60, NOW + 3600),
+            challenge("by_hand", true, 0, 0)
+        ], i128::from(NOW));
+
+        assert_eq!(names(scheduled, |entry| &entry.0), vec!["overdue", "upcoming"]);
+        assert_eq!(names(deployed, |entry| &entry.0), vec!["overdue", "running", "upcoming"]);
+    }
+
+    #[test]
+    fn destroyed_challenges_stay_down_after_a_restart() {
+        // what cmd_destroy leaves behind: not running, and ended when it was destroyed
+        let destroyed = challenge("destroyed", false, NOW - 600, NOW - 10);
+        let (scheduled, deployed) = restored_queues(&[destroyed], i128::from(NOW));
+
+        assert!(scheduled.is_empty());
+        assert!(deployed.is_empty());
+    }
+
+    #[test]
+    fn retired_challenges_stay_down_after_a_restart() {
+        let (scheduled, deployed) = restored_queues(&[challenge("retired", false, NOW - 7200, NOW - 3600), challenge("never", false, 0, 0)], i128::from(NOW));
 
-    if deploy_status == "fail" {
-        timer_queue.deployed_queue.retain(|deployed_challenge| &deployed_challenge.0 != challenge_name);
+        assert!(scheduled.is_empty());
+        assert!(deployed.is_empty());
     }
 }
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+// A round plan releases each wave of challenges back to back:
+// wave N starts at start_time + N * (round_length + gap) and lasts round_length seconds.
+#[derive(serde::Deserialize)]
+pub struct RoundPlan {
+    pub start_time: i128,
+    pub round_length: i128,
+    pub gap: i128,
+    pub waves: Vec<Vec<String>>
+}
+
+pub struct PlannedRelease {
+    pub challenge_name: String,
+    pub start_time: i128,
+    pub end_time: i128
+}
+
+pub fn plan_rounds(plan: &RoundPlan) -> Vec<PlannedRelease> {
+    let mut releases: Vec<PlannedRelease> = vec![];
+
+    for (wave_index, wave) in plan.waves.iter().enumerate() {
+        let wave_start = plan.start_time + (wave_index as i128) * (plan.round_length + plan.gap);
+        let wave_end = wave_start + plan.round_length;
+
+        for challenge_name in wave {
+            releases.push(PlannedRelease {
+                challenge_name: challenge_name.trim().to_string(),
+                start_time: wave_start,
+                end_time: wave_end
+            });
+        }
+    }
+
+    releases
+}
+
+// a challenge can only be scheduled once, the second release would silently replace the first
+pub fn find_repeated_challenge(releases: &[PlannedRelease]) -> Option<&str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+
+    releases.iter()
+        .map(|release| release.challenge_name.as_str())
+        .find(|challenge_name| !seen.insert(challenge_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(waves: &[&[&str]]) -> RoundPlan {
+        RoundPlan {
+            start_time: 1000,
+            round_length: 3600,
+            gap: 600,
+            waves: waves.iter().map(|wave| wave.iter().map(|name| name.to_string()).collect()).collect()
+        }
+    }
+
+    #[test]
+    fn waves_run_back_to_back_with_a_gap() {
+        let releases = plan_rounds(&plan(&[&["a", "b"], &["c"], &["d"]]));
+        let times: Vec<(&str, i128, i128)> = releases.iter().map(|release| (release.challenge_name.as_str(), release.start_time, release.end_time)).collect();
+
+        assert_eq!(times, vec![
+            ("a", 1000, 4600),
+            ("b", 1000, 4600),
+            ("c", 5200, 8800),
+            ("d", 9400, 13000)
+        ]);
+    }
+
+    #[test]
+    fn names_are_trimmed() {
+        let releases = plan_rounds(&plan(&[&[" pwn1 "]]));
+
+        assert_eq!(releases[0].challenge_name, "pwn1");
+    }
+
+    #[test]
+    fn empty_waves_still_take_their_slot() {
+        let releases = plan_rounds(&plan(&[&[], &["late"]]));
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].start_time, 5200);
+    }
+
+    #[test]
+    fn challenges_planned_twice_are_found() {
+        assert_eq!(find_repeated_challenge(&plan_rounds(&plan(&[&["a", "b"], &["c", " a"]]))), Some("a"));
+        assert_eq!(find_repeated_challenge(&plan_rounds(&plan(&[&["a", "b"], &["c"]]))), None);
+    }
+
+    #[test]
+    fn no_waves_no_releases() {
+        assert!(plan_rounds(&plan(&[])).is_empty());
+    }
+}
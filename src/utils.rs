@@ -1,36 +1,66 @@
-use std::{process::Command, fs};
+use std::{collections::BTreeMap, process::Command, fs};
 use std::time::{SystemTime, UNIX_EPOCH};
 use core::cmp::Ordering;
 
-use crate::database::user::UserInstance;
+use crate::database::{score_history::{HintEvent, SolveEvent, StageEvent}, stage::ChallengeStage, team::TeamMember};
 use crate::database::DbConnection;
+use crate::scoring::blood_badge;
 
-#[derive(Clone, Eq, PartialEq, Ord)]
+#[derive(Clone, Eq, PartialEq)]
 pub struct ScoreBoardUser {
     pub place: i32,
     pub username: String,
+    pub team_id: Option<i32>,
+    pub team: Option<String>,
     pub score: u64,
-    pub last_submission: i128
+    pub last_submission: i128,
+    // "challenge solved/total" of multi-stage challenges the user is still working on
+    pub progress: Vec<String>,
+    // one blood badge per challenge the user was among the first solvers of
+    pub badges: Vec<&'static str>
 }
 
-// impl Ord for ScoreBoardUser {
-//     fn cmp(&self, other: &Self) -> Ordering {
-//         self.score
-//             .cmp(&other.score)
-//             .then(self.last_submission.cmp(&other.last_submission).reverse())
-//             //now what?
-//     }
-// }
+// players ranked together, a player without a team is a team of one named after them
+#[derive(Clone)]
+pub struct ScoreBoardTeam {
+    pub place: i32,
+    pub name: String,
+    pub score: u64,
+    pub last_submission: i128,
+    pub badges: Vec<&'static str>,
+    // what every member brought in
+    pub members: Vec<ScoreBoardUser>
+}
 
-impl PartialOrd for ScoreBoardUser {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.score
+// highest score first, then whoever got there first
+impl Ord for ScoreBoardUser {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
             .cmp(&other.score).reverse()
             .then(self.last_submission.cmp(&other.last_submission))
-        )
+            .then(self.username.cmp(&other.username))
     }
 }
 
+impl PartialOrd for ScoreBoardUser {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct ScorePoint {
+    pub time: i64,
+    pub score: i64
+}
+
+// a player's or a team's score after each change, oldest first
+#[derive(serde::Serialize)]
+pub struct ScoreSeries {
+    pub name: String,
+    pub points: Vec<ScorePoint>
+}
+
 
 
 const MIN_START_TIME: i128 = 60 * 1;
@@ -88,55 +118,222 @@ pub fn is_time_schedule_valid(start_time: i128, end_time: i128) -> bool {
     return true;
 }
 
-pub async fn get_scoreboard_from_user_vec(db_conn: DbConnection, users: Vec<UserInstance>) -> Vec<ScoreBoardUser> {
+// every player ranked by score, the earlier last solve first on a tie. one query, see db_get_scoreboard_rows
+pub async fn get_scoreboard(db_conn: &DbConnection) -> Vec<ScoreBoardUser> {
+    let mut scoreboard_users: Vec<ScoreBoardUser> = db_conn.get_scoreboard_rows().await.into_iter()
+        .map(|row| {
+            let earned = u64::try_from(row.solved_score + row.partial_score + row.bonus).unwrap_or_default();
+            ScoreBoardUser {
+                place: 0,
+                username: row.username,
+                team_id: row.team_id,
+                team: row.team_name,
+                score: earned.saturating_sub(u64::try_from(row.hint_penalty).unwrap_or_default()),
+                last_submission: i128::from(row.last_submission),
+                progress: row.progress,
+                badges: row.blood_positions.into_iter().filter_map(blood_badge).collect()
+            }
+        })
+        .collect();
 
-    let mut scoreboard_users: Vec<ScoreBoardUser> = vec![];
+    scoreboard_users.sort();
+    for (i, user) in scoreboard_users.iter_mut().enumerate() {
+        user.place = i32::try_from(i + 1).unwrap_or(i32::MAX);
+    }
+    scoreboard_users
+}
 
-    for user in users {
-        let mut total_score: u64 = 0;
-        for chall_name in user.challenge_solved {
-            let chall = db_conn.get_challenge_by_name(chall_name).await;
-            if chall.running {
-                total_score += u64::try_from(chall.score).unwrap();
-            }
-        }
+// teams ranked by the sum of their members' scores, a solve counts once per team so nothing is counted twice
+pub fn get_team_scoreboard(scoreboard_users: &[ScoreBoardUser]) -> Vec<ScoreBoardTeam> {
+    // keyed by team id, players without a team by their username, so a team can't take a player's place
+    let mut by_team: BTreeMap<(Option<i32>, String), ScoreBoardTeam> = BTreeMap::new();
+    for user in scoreboard_users {
+        let key = match user.team_id {
+            Some(team_id) => (Some(team_id), String::new()),
+            None => (None, user.username.clone())
+        };
 
-        scoreboard_users.push(ScoreBoardUser {
+        let team = by_team.entry(key).or_insert(ScoreBoardTeam {
             place: 0,
-            username: user.username,
-            score: total_score,
-            last_submission: i128::from(user.last_submission)
+            name: user.team.clone().unwrap_or(user.username.clone()),
+            score: 0,
+            last_submission: 0,
+            badges: vec![],
+            members: vec![]
         });
+        team.score += user.score;
+        team.last_submission = team.last_submission.max(user.last_submission);
+        team.badges.extend(user.badges.iter());
+        team.members.push(user.clone());
     }
 
-    // scoreboard_users.sort_by(|a, b| a.score.cmp(&b.score).reverse());
+    let mut scoreboard_teams: Vec<ScoreBoardTeam> = by_team.into_values().collect();
+    scoreboard_teams.sort_by(|a, b| b.score.cmp(&a.score).then(a.last_submission.cmp(&b.last_submission)));
+    for (i, team) in scoreboard_teams.iter_mut().enumerate() {
+        team.place = i32::try_from(i + 1).unwrap_or(i32::MAX);
+        team.members.sort_by_key(|member| std::cmp::Reverse(member.score));
+    }
+    scoreboard_teams
+}
 
-    scoreboard_users.sort();
-    let mut i: usize = 1;
-    let mut final_scoreboard_users: Vec<ScoreBoardUser> = vec![];
-    while i <= scoreboard_users.len() {
-        let user = scoreboard_users.get(i-1).unwrap();
-        let mut user = user.clone();
+// replays the solve events of every (name, members) group, a team or a single player, so the last point
+// matches the scoreboard. challenges are worth what they are worth now, like the scoreboard counts them
+pub async fn get_score_history(db_conn: &DbConnection, groups: Vec<(String, Vec<String>)>) -> Vec<ScoreSeries> {
+    let solves = db_conn.get_solve_events().await;
+    let stages = db_conn.get_stage_events().await;
+    let hints = db_conn.get_hint_events().await;
+    let members = db_conn.get_all_team_members().await;
 
-        user.place = i32::try_from(i).unwrap();
-        final_scoreboard_users.push(user);
-        i += 1;
-    }
+    groups.into_iter().map(|(name, usernames)| {
+        let mut changes: Vec<(i64, i64)> = usernames.iter()
+            .flat_map(|username| score_changes(username, &teammates_of(username, &members), &solves, &stages, &hints))
+            .collect();
+        changes.sort_by_key(|change| change.0);
+
+        let mut score: i64 = 0;
+        let points = changes.into_iter().map(|(time, change)| {
+            score += change;
+            ScorePoint { time, score: score.max(0) }
+        }).collect();
 
-    final_scoreboard_users.to_vec()
+        ScoreSeries { name, points }
+    }).collect()
 }
 
-pub async fn get_user_score(db_conn: DbConnection, user_id: i32) -> u64 {
+fn teammates_of(username: &str, members: &[TeamMember]) -> Vec<String> {
+    match members.iter().find(|member| member.username == username) {
+        Some(member) => members.iter()
+            .filter(|teammate| teammate.team_id == member.team_id)
+            .map(|teammate| teammate.username.clone())
+            .collect(),
+        None => vec![username.to_string()]
+    }
+}
+
+// (time, points) of every solve, stage and hint of one player. stage shares count until the player's team
+// solves the whole challenge, like the partial scores of db_get_scoreboard_rows
+fn score_changes(username: &str, teammates: &[String], solves: &[SolveEvent], stages: &[StageEvent], hints: &[HintEvent]) -> Vec<(i64, i64)> {
+    let solved_at = |challenge_name: &str| solves.iter()
+        .filter(|solve| solve.challenge_name == challenge_name && teammates.contains(&solve.username))
+        .map(|solve| solve.time)
+        .min();
+
+    let mut changes: Vec<(i64, i64)> = solves.iter()
+        .filter(|solve| solve.username == username)
+        .map(|solve| (solve.time, i64::from(solve.points) + i64::from(solve.bonus)))
+        .collect();
+
+    // weight solved so far and what it is worth, per challenge
+    let mut partial: BTreeMap<&str, (i64, i64)> = BTreeMap::new();
+    for stage in stages.iter().filter(|stage| stage.username == username) {
+        if solved_at(&stage.challenge_name).is_some_and(|time| time <= stage.time) {
+            continue;
+        }
 
-    let user: UserInstance = db_conn.get_user_by_id(user_id, true).await;
+        let (weight, share) = partial.entry(&stage.challenge_name).or_default();
+        *weight += i64::from(stage.weight);
+        let new_share = i64::from(stage.points) * *weight / stage.total_weight.max(1);
+        changes.push((stage.time, new_share - *share));
+        *share = new_share;
+    }
 
-    let mut total_score: u64 = 0;
-    for chall_name in user.challenge_solved {
-        let chall = db_conn.get_challenge_by_name(chall_name).await;
-        if chall.running {
-            total_score += u64::try_from(chall.score).unwrap();
+    // the whole challenge takes the place of its stages
+    for (challenge_name, (_, share)) in partial {
+        if let Some(time) = solved_at(challenge_name) {
+            changes.push((time, -share));
         }
     }
 
-    return total_score;
-}
\ No newline at end of file
+    changes.extend(hints.iter()
+        .filter(|hint| hint.username == username)
+        .map(|hint| (hint.time, -i64::from(hint.cost))));
+
+    changes
+}
+
+pub fn stages_by_challenge(stages: Vec<ChallengeStage>) -> BTreeMap<String, Vec<ChallengeStage>> {
+    let mut by_challenge: BTreeMap<String, Vec<ChallengeStage>> = BTreeMap::new();
+    for stage in stages {
+        by_challenge.entry(stage.challenge_name.clone()).or_default().push(stage);
+    }
+    by_challenge
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(username: &str, team: Option<(i32, &str)>, score: u64, last_submission: i128) -> ScoreBoardUser {
+        ScoreBoardUser {
+            place: 0,
+            username: username.to_string(),
+            team_id: team.map(|(id, _)| id),
+            team: team.map(|(_, name)| name.to_string()),
+            score,
+            last_submission,
+            progress: vec![],
+            badges: vec![]
+        }
+    }
+
+    fn ranking(teams: &[ScoreBoardTeam]) -> Vec<(i32, &str, u64)> {
+        teams.iter().map(|team| (team.place, team.name.as_str(), team.score)).collect()
+    }
+
+    #[test]
+    fn members_add_up_to_their_team() {
+        let teams = get_team_scoreboard(&[
+            user("alice", Some((1, "red")), 300, 10),
+            user("bob", Some((1, "red")), 200, 30),
+            user("carol", Some((2, "blue")), 400, 20)
+        ]);
+
+        assert_eq!(ranking(&teams), vec![(1, "red", 500), (2, "blue", 400)]);
+        assert_eq!(teams[0].last_submission, 30);
+        assert_eq!(teams[0].members.iter().map(|member| member.username.as_str()).collect::<Vec<_>>(), vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn players_without_a_team_rank_alone() {
+        let teams = get_team_scoreboard(&[
+            user("alice", Some((1, "red")), 100, 10),
+            user("dave", None, 150, 10),
+            user("erin", None, 50, 10)
+        ]);
+
+        assert_eq!(ranking(&teams), vec![(1, "dave", 150), (2, "red", 100), (3, "erin", 50)]);
+    }
+
+    #[test]
+    fn teams_named_like_a_player_stay_apart() {
+        let teams = get_team_scoreboard(&[
+            user("alice", Some((1, "dave")), 100, 10),
+            user("dave", None, 100, 20)
+        ]);
+
+        assert_eq!(teams.len(), 2);
+        assert_eq!(teams[0].members[0].username, "alice");
+        assert_eq!(teams[1].members[0].username, "dave");
+    }
+
+    #[test]
+    fn ties_go_to_whoever_got_there_first() {
+        let teams = get_team_scoreboard(&[
+            user("alice", Some((1, "red")), 100, 50),
+            user("bob", Some((2, "blue")), 100, 40)
+        ]);
+
+        assert_eq!(ranking(&teams), vec![(1, "blue", 100), (2, "red", 100)]);
+    }
+
+    #[test]
+    fn badges_are_collected_from_every_member() {
+        let mut alice = user("alice", Some((1, "red")), 100, 10);
+        alice.badges = vec!["🥇"];
+        let mut bob = user("bob", Some((1, "red")), 50, 10);
+        bob.badges = vec!["🥈", "🥉"];
+
+        let teams = get_team_scoreboard(&[alice, bob]);
+
+        assert_eq!(teams[0].badges, vec!["🥇", "🥈", "🥉"]);
+    }
+}
@@ -0,0 +1,154 @@
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, offset::Utc};
+
+use crate::database::{challenge::ChallengeData, DbConnection};
+
+const ICS_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+// RFC 5545 wants content lines folded at 75 octets
+const ICS_MAX_LINE_LENGTH: usize = 75;
+
+pub async fn api_upcoming_ics(db_conn: web::Data<DbConnection>) -> Result<HttpResponse, actix_web::Error> {
+    let mut challs = db_conn.get_all_running_challenges().await;
+    challs.append(&mut db_conn.get_upcoming_challenges().await);
+
+    let resp = HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header(("Content-Disposition", "inline; filename=\"livectf.ics\""))
+        .body(build_calendar(&challs));
+
+    Ok(resp)
+}
+
+fn build_calendar(challs: &[ChallengeData]) -> String {
+    let now = Utc::now().format(ICS_DATE_FORMAT).to_string();
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//CoSGang//livectf//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:CoSGang livectf".to_string(),
+    ];
+
+    for chall in challs {
+        // challenges without a schedule (e.g. failed builds) are not worth announcing
+        if chall.start_time <= 0 || chall.end_time <= 0 {
+            continue;
+        }
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@livectf", chall.challenge_name));
+        lines.push(format!("DTSTAMP:{}", now));
+        lines.push(format!("DTSTART:{}", format_ics_time(chall.start_time)));
+        lines.push(format!("DTEND:{}", format_ics_time(chall.end_time)));
+        lines.push(format!("SUMMARY:{}", escape_ics_text(&format!("[{}] {}", chall.category, chall.challenge_name))));
+        lines.push(format!("CATEGORIES:{}", escape_ics_text(&chall.category)));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = String::new();
+    for line in lines {
+        calendar.push_str(&fold_ics_line(&line));
+        calendar.push_str("\r\n");
+    }
+
+    calendar
+}
+
+fn format_ics_time(epoch: i64) -> String {
+    let time: DateTime<Utc> = DateTime::from_timestamp(epoch, 0).unwrap_or(DateTime::from_timestamp(0, 0).unwrap());
+    time.format(ICS_DATE_FORMAT).to_string()
+}
+
+fn escape_ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_length = 0;
+
+    for c in line.chars() {
+        if line_length + c.len_utf8() > ICS_MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // the leading space of a continuation line counts towards its length
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(name: &str, category: &str, start_time: i64, end_time: i64) -> ChallengeData {
+        ChallengeData {
+            id: 1,
+            challenge_name: name.to_string(),
+            score: 500,
+            category: category.to_string(),
+            solved_by: vec![],
+            running: false,
+            connection_string: String::new(),
+            start_time,
+            end_time
+        }
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape_ics_text("a\\b;c,d\ne"), "a\\\\b\\;c\\,d\\ne");
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let line = "x".repeat(ICS_MAX_LINE_LENGTH);
+
+        assert_eq!(fold_ics_line(&line), line);
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        let folded = fold_ics_line(&"x".repeat(200));
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() <= ICS_MAX_LINE_LENGTH));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), "x".repeat(200));
+    }
+
+    #[test]
+    fn folding_never_splits_a_character() {
+        let folded = fold_ics_line(&"é".repeat(100));
+
+        assert!(folded.split("\r\n").all(|line| line.len() <= ICS_MAX_LINE_LENGTH));
+        assert_eq!(folded.replace("\r\n ", ""), "é".repeat(100));
+    }
+
+    #[test]
+    fn formats_times_in_utc() {
+        assert_eq!(format_ics_time(1700000000), "20231114T221320Z");
+    }
+
+    #[test]
+    fn only_scheduled_challenges_become_events() {
+        let calendar = build_calendar(&[challenge("pwn,1", "pwn;web", 1700000000, 1700003600), challenge("broken", "misc", 0, 0)]);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+        assert!(calendar.contains("SUMMARY:[pwn\\;web] pwn\\,1\r\n"));
+        assert!(calendar.contains("DTSTART:20231114T221320Z\r\nDTEND:20231114T231320Z\r\n"));
+        assert!(!calendar.contains("broken"));
+    }
+}
@@ -1,9 +1,12 @@
 use actix_web::{HttpResponse, web, HttpRequest, cookie::Cookie};
 use std::collections::BTreeMap;
 
-use crate::notifier::{NotifierComms, craft_type_notify_message};
+use crate::notifier::{NotifierComms, RequestError, Responder};
+use crate::deployer::{DeployerCommand, DeployerReply, DEPLOYER_REQUEST_TIMEOUT};
+use crate::timer::{TimerCommand, TIMER_REQUEST_TIMEOUT};
 use crate::web_interface::{get_jwt_claims, get_error, success, unauthorized, forbiden};
 use crate::utils::{is_time_schedule_valid, MAGIC_TIME, is_challenge_exists, check_if_challenge_is_up};
+use crate::timer::rounds::{find_repeated_challenge, plan_rounds, RoundPlan};
 
 pub async fn api_challenge_action(slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, actix_web::Error> {
     let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
@@ -53,11 +56,14 @@ pub async fn api_challenge_action(slaves: web::Data<NotifierComms>, req: HttpReq
                 return Ok(get_error("Please adjust start_time/end_time"));
             }
 
-            let target_module = String::from("deployer");
-            let data = craft_type_notify_message(&target_module, &["schedule", challenge_name, &start_time.to_string(), &end_time.to_string()]);
-            slaves.notify(target_module, data);
-            return Ok(success("Challenge scheduled"))
+            let reply = slaves.request(|reply| DeployerCommand::Schedule {
+                challenge_filename: challenge_name.to_string(),
+                start_time,
+                end_time,
+                reply
+            }, DEPLOYER_REQUEST_TIMEOUT).await;
 
+            Ok(deployer_reply_to_response(reply, "Challenge scheduled"))
         },
         "destroy" => {
             if !is_challenge_exists(challenge_name) {
@@ -66,13 +72,120 @@ pub async fn api_challenge_action(slaves: web::Data<NotifierComms>, req: HttpReq
                 return Ok(get_error("Challenge is not started"));
             }
 
-            let target_module = String::from("deployer");
-            let data = craft_type_notify_message(&target_module, &["destroy", &challenge_name]);
-            slaves.notify(target_module, data);
+            let reply = slaves.request(|reply| DeployerCommand::Destroy {
+                challenge_filename: challenge_name.to_string(),
+                reply
+            }, DEPLOYER_REQUEST_TIMEOUT).await;
 
-            return Ok(success("Challenge destroyed"));
+            Ok(deployer_reply_to_response(reply, "Challenge destroyed"))
         },
         _ => Ok(get_error("Unknown action"))
     }
 
-}
\ No newline at end of file
+}
+
+pub async fn api_plan_rounds(slaves: web::Data<NotifierComms>, req: HttpRequest, plan: web::Json<RoundPlan>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let mut plan = plan.into_inner();
+    plan.start_time -= MAGIC_TIME;
+
+    if plan.waves.is_empty() || plan.waves.iter().any(|wave| wave.is_empty()) {
+        return Ok(get_error("Every wave needs at least one challenge"));
+    }
+
+    if plan.round_length <= 0 {
+        return Ok(get_error("Rounds need to last at least a second"));
+    }
+
+    if plan.gap < 0 {
+        return Ok(get_error("Gap between rounds can't be negative"));
+    }
+
+    let releases = plan_rounds(&plan);
+
+    if let Some(challenge_name) = find_repeated_challenge(&releases) {
+        return Ok(get_error(&format!("Challenge planned more than once: {}", challenge_name)));
+    }
+
+    // validate the whole plan first so we never end up with half of it scheduled
+    for release in &releases {
+        if !is_challenge_exists(&release.challenge_name) {
+            return Ok(get_error(&format!("Challenge does not exist: {}", release.challenge_name)));
+        } else if check_if_challenge_is_up(&release.challenge_name) {
+            return Ok(get_error(&format!("Challenge already started: {}", release.challenge_name)));
+        }
+
+        if !is_time_schedule_valid(release.start_time, release.end_time) {
+            return Ok(get_error(&format!("Please adjust the plan, {} can't be scheduled at that time", release.challenge_name)));
+        }
+    }
+
+    for release in &releases {
+        slaves.notify(DeployerCommand::Schedule {
+            challenge_filename: release.challenge_name.to_string(),
+            start_time: release.start_time,
+            end_time: release.end_time,
+            // building a whole plan takes longer than anyone wants to wait on a request
+            reply: Responder::none()
+        });
+    }
+
+    Ok(success(&format!("{} challenges scheduled over {} rounds", releases.len(), plan.waves.len())))
+}
+
+pub async fn api_event_action(slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(String,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let action = &path.0;
+
+    match action.as_str() {
+        "pause" => {
+            match slaves.request(|reply| TimerCommand::Pause { reply }, TIMER_REQUEST_TIMEOUT).await {
+                Ok(true) => Ok(success("Event paused")),
+                Ok(false) => Ok(get_error("Event is already paused")),
+                Err(err) => Ok(get_error(&format!("Timer failed: {}", err)))
+            }
+        },
+        "resume" => {
+            match slaves.request(|reply| TimerCommand::Resume { reply }, TIMER_REQUEST_TIMEOUT).await {
+                Ok(true) => Ok(success("Event resumed")),
+                Ok(false) => Ok(get_error("Event is not paused")),
+                Err(err) => Ok(get_error(&format!("Timer failed: {}", err)))
+            }
+        },
+        _ => Ok(get_error("Unknown action"))
+    }
+}
+
+pub(crate) fn deployer_reply_to_response(reply: Result<DeployerReply, RequestError>, success_msg: &str) -> HttpResponse {
+    match reply {
+        Ok(DeployerReply::Failed(reason)) => get_error(&format!("Deployer failed: {}", reason)),
+        Ok(_) => success(success_msg),
+        // the deployer keeps working on it, we just stopped waiting
+        Err(RequestError::Timeout) => success(&format!("{} (still in progress)", success_msg)),
+        Err(err) => get_error(&format!("Deployer failed: {}", err))
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
+
+use crate::database::{hint::HintData, DbConnection};
+use crate::events::DomainEvent;
+use crate::notifier::NotifierComms;
+use crate::web_interface::{get_jwt_claims, forbiden, unauthorized, get_error, success};
+
+// what a player sees of a hint, the content only once it's unlocked
+#[derive(serde::Serialize)]
+struct PlayerHint {
+    id: i32,
+    cost: i32,
+    released: bool,
+    release_at: i64,
+    unlocked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>
+}
+
+#[derive(serde::Serialize)]
+struct HintUnlockResponse {
+    is_error: bool,
+    message: String,
+    content: String
+}
+
+fn release_at(hint: &HintData, start_time: i64) -> i64 {
+    start_time + hint.release_after
+}
+
+pub async fn api_hint_create(db_conn: web::Data<DbConnection>, req: HttpRequest, hint: web::Json<HintData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let hint = hint.into_inner();
+
+    if hint.challenge_name.trim().is_empty() || hint.content.trim().is_empty() {
+        return Ok(get_error("Hint needs a challenge and content"));
+    }
+
+    if hint.cost < 0 || hint.release_after < 0 {
+        return Ok(get_error("Cost and release time can't be negative"));
+    }
+
+    if !db_conn.create_hint(hint).await {
+        return Ok(get_error("Can't create hint"));
+    }
+
+    Ok(success("Hint created!"))
+}
+
+pub async fn api_hint_delete(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    if !db_conn.delete_hint(path.0).await {
+        return Ok(get_error("Hint does not exist"));
+    }
+
+    // players get back what they spent on it
+    slaves.notify(DomainEvent::ScoreChanged { reason: "a hint was deleted".to_string() });
+    Ok(success("Hint deleted!"))
+}
+
+pub async fn api_challenge_hints(db_conn: web::Data<DbConnection>, req: HttpRequest, path: web::Path<(String,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let challenge = db_conn.get_challenge_by_name(path.0.clone()).await;
+    if !challenge.running {
+        return Ok(get_error("Challenge is not running"));
+    }
+
+    let now = chrono::offset::Utc::now().timestamp();
+    let unlocked_ids = db_conn.get_unlocked_hint_ids(username).await;
+
+    let hints: Vec<PlayerHint> = db_conn.get_hints_for_challenge(challenge.challenge_name).await.into_iter()
+        .map(|hint| {
+            let unlocked = unlocked_ids.contains(&hint.id);
+            let release_at = release_at(&hint, challenge.start_time);
+            PlayerHint {
+                id: hint.id,
+                cost: hint.cost,
+                released: now >= release_at,
+                release_at,
+                unlocked,
+                content: unlocked.then_some(hint.content)
+            }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(hints))
+}
+
+pub async fn api_hint_unlock(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let hint = match db_conn.get_hint_by_id(path.0).await {
+        Some(hint) => hint,
+        None => return Ok(get_error("Hint does not exist"))
+    };
+
+    let challenge = db_conn.get_challenge_by_name(hint.challenge_name.clone()).await;
+    if !challenge.running {
+        return Ok(get_error("Challenge is not running"));
+    }
+
+    if chrono::offset::Utc::now().timestamp() < release_at(&hint, challenge.start_time) {
+        return Ok(get_error("Hint is not released yet"));
+    }
+
+    // unlocking twice is free, the player just gets the content again
+    match db_conn.unlock_hint(hint.id, username.clone()).await {
+        Ok(true) => slaves.notify(DomainEvent::HintUnlocked {
+            challenge_name: hint.challenge_name,
+            username,
+            cost: hint.cost
+        }),
+        Ok(false) => {},
+        Err(_) => return Ok(get_error("Could not unlock the hint, try again"))
+    }
+
+    Ok(HttpResponse::Ok().json(HintUnlockResponse {
+        is_error: false,
+        message: "Hint unlocked!".to_string(),
+        content: hint.content
+    }))
+}
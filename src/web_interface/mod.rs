@@ -3,24 +3,41 @@ use actix_web::{http::header::ContentType, web, HttpResponse, Result as ActixRes
 use maud::{html, Markup};
 use jwt::{Error as JWT_Error, SignWithKey, VerifyWithKey};
 use hmac::{Hmac, Mac};
-use crate::database::{solve_history::SolveHistoryEntry, user::UserInstance, DbFilter};
+use crate::database::{event_log::EventLogEntry, flag_review::FlagReview, hint::HintData, settings::{EVENT_END_SETTING, KNOWN_SETTINGS}, team::{TeamData, TeamMember}, solve_history::SolveHistoryEntry, user::UserInstance, webhook::{WebhookData, WebhookDelivery}, DbFilter};
 use std::{collections::BTreeMap, os::unix::fs::MetadataExt, vec};
 use sha2::Sha256;
 use std::fs;
-use chrono::{DateTime, offset::Utc};
+use chrono::{DateTime, NaiveDateTime, offset::Utc};
 // use futures_util::lock::Mutex;
 // use uuid::Uuid;
 
-use crate::{database::DbConnection, utils};
+use crate::{database::DbConnection, scoreboard_cache::ScoreboardCache, scoring::blood_badge, supervisor::{ModuleHealth, ModuleStatus, Supervisor}, utils::{self, MAGIC_TIME}, webhook::WEBHOOK_TOPICS};
 
 pub mod user;
 pub mod challenge;
+pub mod calendar;
+pub mod webhook;
+pub mod settings;
+pub mod hint;
+pub mod team;
+pub mod scoreboard;
 
 const USER_PATH: &str = "users";
 const SOLVE_LOG_PATH: &str = "solve-logs";
 const CHALLENGE_PATH: &str = "challenges";
 const CHALLENGE_UPLOAD_PATH: &str = "challenge-upload";
 const CHALLENGE_SCHEDULE_PATH: &str = "challenge-schedule";
+const CHALLENGE_ROUNDS_PATH: &str = "challenge-rounds";
+const MODULES_PATH: &str = "modules";
+const EVENT_LOG_PATH: &str = "event-log";
+const EVENT_LOG_LIMIT: i32 = 200;
+const WEBHOOKS_PATH: &str = "webhooks";
+const WEBHOOK_DELIVERY_LIMIT: i32 = 50;
+const SETTINGS_PATH: &str = "settings";
+const FLAG_REVIEWS_PATH: &str = "flag-reviews";
+const FLAG_REVIEW_LIMIT: i32 = 100;
+const HINTS_PATH: &str = "hints";
+const TEAMS_PATH: &str = "teams";
 
 #[derive(serde::Serialize)]
 pub struct JsonResponse {
@@ -30,7 +47,11 @@ pub struct JsonResponse {
 
 #[derive(serde::Deserialize)]
 pub struct PaginationQuery {
-    path: Option<String>
+    path: Option<String>,
+    // event log filters
+    source: Option<String>,
+    topic: Option<String>,
+    since: Option<String>
 }
 
 // TODO: randomize this, store in env
@@ -209,6 +230,7 @@ pub async fn index() -> ActixResult<Markup> {
                     div class="nav-bar" {
                         a href="/scoreboard" { "Scoreboard" }
                         a href="/challenges" { "Challenges" }
+                        a href="/upcoming" { "Upcoming" }
                     }
                 }
             }
@@ -216,10 +238,66 @@ pub async fn index() -> ActixResult<Markup> {
     })
 }
 
+pub async fn upcoming(db_conn: web::Data<DbConnection>) -> ActixResult<Markup> {
+    let challs = db_conn.get_upcoming_challenges().await;
+
+    Ok(html! {
+        html {
+            head {
+                link rel="stylesheet" href="/static/css/styles.css" {}
+                link rel="stylesheet" href="/static/css/styles_upcoming.css" {}
+                meta charset="utf-8" {}
+                title {
+                    "CoSGang livectf - Upcoming"
+                }
+            }
+            body {
+                div class="container" {
+                    nav class="page-nav" {
+                        a href="/challenges" { "Challenges" }
+                        a href="/upcoming.ics" { "Add to calendar" }
+                    }
+                    h1 style="margin-bottom: 20px;" { "Upcoming" }
+                    div class="wrapper" {
+                        @if challs.is_empty() {
+                            p { "Nothing scheduled yet, stay tuned!" }
+                        }
+                        @for chall in challs {
+                            div class="release-wrapper" data-start=(chall.start_time) {
+                                h3 { (chall.challenge_name) }
+                                div class="release-metadata" {
+                                    p class="release-category" { (chall.category) }
+                                    p class="release-countdown" { "--:--:--" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            script src="/static/js/upcoming.js" {}
+        }
+    })
+}
+
 // TODO: add advanced search and delete feature to solve logs
-pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<DbConnection>, req: HttpRequest) -> ActixResult<Markup> {
+pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<DbConnection>, supervisor: web::Data<Supervisor>, req: HttpRequest) -> ActixResult<Markup> {
     let path = page.path.clone().unwrap_or(String::from("/"));
     let mut users: Vec<UserInstance> = vec![];
+    let mut modules: Vec<ModuleHealth> = vec![];
+    let mut event_logs: Vec<EventLogEntry> = vec![];
+    let mut webhooks: Vec<WebhookData> = vec![];
+    let mut webhook_deliveries: Vec<WebhookDelivery> = vec![];
+    let mut event_end: i64 = 0;
+    let mut flag_reviews: Vec<FlagReview> = vec![];
+    let mut hints: Vec<HintData> = vec![];
+    let mut teams: Vec<TeamData> = vec![];
+    let mut team_members: Vec<TeamMember> = vec![];
+    let mut limit_settings: Vec<(&str, &str, i64)> = vec![];
+    let mut event_scoring = String::new();
+    let mut blood_bonus = String::new();
+    let event_source = page.source.clone().unwrap_or_default();
+    let event_topic = page.topic.clone().unwrap_or_default();
+    let event_since = page.since.clone().unwrap_or_default();
     let mut solve_logs: Vec<SolveHistoryEntry> = vec![];
     let mut challenges: Vec<(String, DateTime<Utc>, usize, usize, bool)> = vec![];
     
@@ -248,6 +326,33 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
         users = db_conn.get_all_user().await;
     } else if path == SOLVE_LOG_PATH {
         solve_logs = db_conn.fetch_recent_solve_log(20).await;
+    } else if path == MODULES_PATH {
+        modules = supervisor.health();
+    } else if path == EVENT_LOG_PATH {
+        // datetime-local input, interpreted as UTC
+        let since = NaiveDateTime::parse_from_str(&event_since, "%Y-%m-%dT%H:%M")
+            .map(|time| time.and_utc().timestamp())
+            .unwrap_or(0);
+        event_logs = db_conn.filter_event_log(event_source.trim(), event_topic.trim(), since, EVENT_LOG_LIMIT).await;
+    } else if path == WEBHOOKS_PATH {
+        webhooks = db_conn.get_all_webhooks().await;
+        webhook_deliveries = db_conn.get_recent_webhook_deliveries(WEBHOOK_DELIVERY_LIMIT).await;
+    } else if path == FLAG_REVIEWS_PATH {
+        flag_reviews = db_conn.get_flag_reviews(FLAG_REVIEW_LIMIT).await;
+    } else if path == HINTS_PATH {
+        hints = db_conn.get_all_hints().await;
+    } else if path == TEAMS_PATH {
+        teams = db_conn.get_all_teams().await;
+        team_members = db_conn.get_all_team_members().await;
+    } else if path == SETTINGS_PATH {
+        let settings = db_conn.get_settings().await;
+        event_end = settings.get(EVENT_END_SETTING);
+        limit_settings = KNOWN_SETTINGS.iter()
+            .filter(|setting| setting.key != EVENT_END_SETTING)
+            .map(|setting| (setting.key, setting.label, settings.get(setting.key)))
+            .collect();
+        event_scoring = serde_json::to_string(&settings.scoring()).unwrap_or_default();
+        blood_bonus = serde_json::to_string(&settings.blood_bonus()).unwrap_or_default();
     } else if path == CHALLENGE_PATH {
         let file_entry = fs::read_dir("./archives/").unwrap();
         for entry in file_entry {
@@ -290,13 +395,21 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
                         div class="menu-wrapper" {
                             a href=(format!("/sheep_center?path={}", USER_PATH)) { "Users management" }
                             a href="/sheep_center?path=challenges" { "Challenges" }
-                            @if vec!(CHALLENGE_PATH, CHALLENGE_SCHEDULE_PATH, CHALLENGE_UPLOAD_PATH).contains(&path.as_str()) {
+                            @if [CHALLENGE_PATH, CHALLENGE_SCHEDULE_PATH, CHALLENGE_UPLOAD_PATH, CHALLENGE_ROUNDS_PATH].contains(&path.as_str()) {
                                 div class="challenge-submenu" {
                                     a href=("/sheep_center?path=".to_string()+CHALLENGE_UPLOAD_PATH) { "Upload" }
                                     a href=("/sheep_center?path=".to_string()+CHALLENGE_SCHEDULE_PATH) { "Schedule" }
+                                    a href=("/sheep_center?path=".to_string()+CHALLENGE_ROUNDS_PATH) { "Rounds" }
                                 }
                             } 
                             a href=(format!("/sheep_center?path={}", SOLVE_LOG_PATH)) { "Solve logs" }
+                            a href=(format!("/sheep_center?path={}", MODULES_PATH)) { "Modules" }
+                            a href=(format!("/sheep_center?path={}", EVENT_LOG_PATH)) { "Event log" }
+                            a href=(format!("/sheep_center?path={}", WEBHOOKS_PATH)) { "Webhooks" }
+                            a href=(format!("/sheep_center?path={}", FLAG_REVIEWS_PATH)) { "Flag reviews" }
+                            a href=(format!("/sheep_center?path={}", HINTS_PATH)) { "Hints" }
+                            a href=(format!("/sheep_center?path={}", TEAMS_PATH)) { "Teams" }
+                            a href=(format!("/sheep_center?path={}", SETTINGS_PATH)) { "Settings" }
                         }
     
                         div class="main-section" {
@@ -347,6 +460,7 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
                                         tr {
                                             th { "ID" }
                                             th { "Challenge" }
+                                            th { "Stage" }
                                             th { "Username" }
                                             th { "Result" }
                                             th { "Time" }
@@ -356,6 +470,7 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
                                             tr {
                                                 td { (log.id()) }
                                                 td { (log.challenge_name()) }
+                                                td { (log.stage()) }
                                                 td { (log.username()) }
                                                 @match log.is_success() {
                                                     true => td class="success-submission" {
@@ -445,6 +560,334 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
                                         }
                                     }
                                 }
+                            } @else if path == CHALLENGE_ROUNDS_PATH {
+                                h1 id="section-title" { "Round planner" }
+                                div class="section-wrapper" {
+                                    div class="form-wrapper" {
+                                        form class="challenge-rounds-form" method="post" {
+                                            input type="date" name="round-start-date" id="round-start-date" {}
+                                            input type="time" name="round-start-time" id="round-start-time" {}
+                                            input type="number" name="round-length" id="round-length" min="1" placeholder="Round length (minutes)" {}
+                                            input type="number" name="round-gap" id="round-gap" min="0" placeholder="Gap between rounds (minutes)" {}
+                                            textarea name="round-waves" id="round-waves" rows="8" placeholder="One wave per line, challenge names separated by commas" {}
+                                            button id="plan-rounds" {
+                                                span {
+                                                    "plan"
+                                                }
+                                            }
+                                        }
+                                    }
+                                    div class="action-btn-wrapper" {
+                                        button id="pause-event" { "Pause event" }
+                                        button id="resume-event" { "Resume event" }
+                                    }
+                                }
+                            } @else if path == EVENT_LOG_PATH {
+                                h1 id="section-title" { "Event log" }
+                                div class="section-wrapper" {
+                                    form class="event-log-filter" method="get" action="/sheep_center" {
+                                        input type="hidden" name="path" value=(EVENT_LOG_PATH) {}
+                                        input type="text" name="source" placeholder="Source module" value=(event_source) {}
+                                        input type="text" name="topic" placeholder="Topic" value=(event_topic) {}
+                                        input type="datetime-local" name="since" value=(event_since) {}
+                                        button type="submit" { "Filter" }
+                                    }
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Time" }
+                                            th { "Source" }
+                                            th { "Topic" }
+                                            th { "Payload" }
+                                        }
+                                        @for log in event_logs {
+                                            tr {
+                                                td { (log.id()) }
+                                                td { (log.time()) }
+                                                td { (log.source()) }
+                                                td { (log.topic()) }
+                                                td class="event-payload" { (log.payload()) }
+                                            }
+                                        }
+                                    }
+                                }
+                            } @else if path == WEBHOOKS_PATH {
+                                h1 id="section-title" { "Webhooks" }
+                                div class="section-wrapper" {
+                                    div class="form-wrapper" {
+                                        form class="webhook-form" method="post" {
+                                            input type="text" name="webhook-url" id="webhook-url" placeholder="https://discord.com/api/webhooks/..." {}
+                                            select name="webhook-format" id="webhook-format" {
+                                                option value="discord" { "Discord" }
+                                                option value="slack" { "Slack" }
+                                                option value="generic" { "Generic JSON" }
+                                            }
+                                            div class="webhook-topics" {
+                                                @for topic in WEBHOOK_TOPICS {
+                                                    label {
+                                                        input type="checkbox" class="webhook-topic" value=(topic) checked {}
+                                                        (topic)
+                                                    }
+                                                }
+                                            }
+                                            input type="text" name="webhook-secret" id="webhook-secret" placeholder="Signing secret (optional)" {}
+                                            textarea name="webhook-template" id="webhook-template" rows="4" placeholder="Generic only, e.g. {\"msg\": \"{{text}}\"}" {}
+                                            button id="create-webhook" { "Add webhook" }
+                                        }
+                                    }
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Url" }
+                                            th { "Format" }
+                                            th { "Topics" }
+                                            th { "Signed" }
+                                            th { "Action" }
+                                        }
+                                        @for webhook in webhooks {
+                                            tr {
+                                                td { (webhook.id) }
+                                                td { (webhook.url) }
+                                                td { (webhook.format) }
+                                                @if webhook.topics.is_empty() {
+                                                    td { "all" }
+                                                } @else {
+                                                    td { (webhook.topics.join(", ")) }
+                                                }
+                                                td { @if webhook.secret.is_empty() { "no" } @else { "yes" } }
+                                                td {
+                                                    div class="action-btn-wrapper" {
+                                                        button class="test-webhook-btn" data-webhookid=(webhook.id) { "Test" }
+                                                        button class="del-webhook-btn" data-webhookid=(webhook.id) { "⛔" }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                    h2 { "Announcement" }
+                                    div class="form-wrapper" {
+                                        form class="announcement-form" method="post" {
+                                            textarea name="announcement" id="announcement" rows="3" placeholder="Sent to every webhook subscribed to announcement" {}
+                                            button id="send-announcement" { "Announce" }
+                                        }
+                                    }
+                                    h2 { "Recent deliveries" }
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Time" }
+                                            th { "Webhook" }
+                                            th { "Topic" }
+                                            th { "Attempts" }
+                                            th { "Status" }
+                                            th { "Error" }
+                                        }
+                                        @for delivery in webhook_deliveries {
+                                            tr {
+                                                td { (delivery.id) }
+                                                td { (delivery.time()) }
+                                                td { (delivery.webhook_id) }
+                                                td { (delivery.topic) }
+                                                td { (delivery.attempts) }
+                                                @if delivery.success {
+                                                    td class="success-submission" { (delivery.status_code) }
+                                                } @else {
+                                                    td class="fail-submission" { (delivery.status_code) }
+                                                }
+                                                td { (delivery.error) }
+                                            }
+                                        }
+                                    }
+                                }
+                            } @else if path == FLAG_REVIEWS_PATH {
+                                h1 id="section-title" { "Flag reviews" }
+                                div class="section-wrapper" {
+                                    p { "Players who submitted a per-player flag that belongs to someone else." }
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Time" }
+                                            th { "Challenge" }
+                                            th { "Submitted by" }
+                                            th { "Flag owner" }
+                                            th { "Flag" }
+                                        }
+                                        @for review in flag_reviews {
+                                            tr {
+                                                td { (review.id) }
+                                                td { (review.time()) }
+                                                td { (review.challenge_name) }
+                                                td { (review.submitted_by) }
+                                                td { (review.owner) }
+                                                td { (review.flag) }
+                                            }
+                                        }
+                                    }
+                                }
+                            } @else if path == HINTS_PATH {
+                                h1 id="section-title" { "Hints" }
+                                div class="section-wrapper" {
+                                    div class="form-wrapper" {
+                                        form class="hint-form" method="post" {
+                                            input type="text" name="hint-challenge" id="hint-challenge" placeholder="Challenge name" {}
+                                            textarea name="hint-content" id="hint-content" rows="3" placeholder="Hint" {}
+                                            input type="number" name="hint-cost" id="hint-cost" min="0" placeholder="Cost in points (0 is free)" {}
+                                            input type="number" name="hint-release" id="hint-release" min="0" placeholder="Released after minutes (0 is right away)" {}
+                                            button id="create-hint" { "Add hint" }
+                                        }
+                                    }
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Challenge" }
+                                            th { "Hint" }
+                                            th { "Cost" }
+                                            th { "Released after" }
+                                            th { "Action" }
+                                        }
+                                        @for hint in hints {
+                                            tr {
+                                                td { (hint.id) }
+                                                td { (hint.challenge_name) }
+                                                td { (hint.content) }
+                                                td { (hint.cost) }
+                                                td { (hint.release_after / 60) " min" }
+                                                td {
+                                                    div class="action-btn-wrapper" {
+                                                        button class="del-hint-btn" data-hintid=(hint.id) { "⛔" }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            } @else if path == TEAMS_PATH {
+                                h1 id="section-title" { "Teams" }
+                                div class="section-wrapper" {
+                                    table class="the-table" {
+                                        tr {
+                                            th { "ID" }
+                                            th { "Name" }
+                                            th { "Captain" }
+                                            th { "Members" }
+                                            th { "Created at" }
+                                            th { "Action" }
+                                        }
+                                        @for team in teams {
+                                            tr {
+                                                td { (team.id) }
+                                                td { (team.name) }
+                                                td { (team.captain) }
+                                                td {
+                                                    (team_members.iter()
+                                                        .filter(|member| member.team_id == team.id)
+                                                        .map(|member| member.username.as_str())
+                                                        .collect::<Vec<&str>>()
+                                                        .join(", "))
+                                                }
+                                                td { (DateTime::from_timestamp(team.created_at, 0).unwrap_or_default()) }
+                                                td {
+                                                    div class="action-btn-wrapper" {
+                                                        button class="del-team-btn" data-teamid=(team.id) { "⛔" }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            } @else if path == SETTINGS_PATH {
+                                h1 id="section-title" { "Settings" }
+                                div class="section-wrapper" {
+                                    p {
+                                        "Submissions close at: "
+                                        @if event_end > 0 {
+                                            (DateTime::from_timestamp(event_end + MAGIC_TIME as i64, 0).unwrap_or_default().format("%Y-%m-%d %H:%M"))
+                                        } @else {
+                                            "never"
+                                        }
+                                    }
+                                    div class="form-wrapper" {
+                                        form class="settings-form" method="post" {
+                                            input type="date" name="event-end-date" id="event-end-date" {}
+                                            input type="time" name="event-end-time" id="event-end-time" {}
+                                            @for (key, label, value) in &limit_settings {
+                                                label {
+                                                    (label)
+                                                    input type="number" class="setting-input" data-key=(key) min="0" value=(value) {}
+                                                }
+                                            }
+                                            button id="save-settings" {
+                                                span {
+                                                    "save"
+                                                }
+                                            }
+                                        }
+                                    }
+                                    div class="action-btn-wrapper" {
+                                        button id="clear-event-end" { "Never close submissions" }
+                                    }
+                                    h2 { "Scoring" }
+                                    p { "Event scoring: " code { (event_scoring) } }
+                                    div class="form-wrapper" {
+                                        form class="scoring-form" method="post" {
+                                            input type="text" name="scoring-challenge" id="scoring-challenge" placeholder="Challenge name (empty for the whole event)" {}
+                                            select name="scoring-kind" id="scoring-kind" {
+                                                option value="dynamic" { "Dynamic (CTFd)" }
+                                                option value="linear" { "Linear decay" }
+                                                option value="logarithmic" { "Logarithmic decay" }
+                                                option value="static" { "Static" }
+                                            }
+                                            input type="number" name="scoring-initial" id="scoring-initial" min="0" placeholder="Initial points, or the points of static scoring" {}
+                                            input type="number" name="scoring-minimum" id="scoring-minimum" min="0" placeholder="Minimum points" {}
+                                            input type="number" name="scoring-decay" id="scoring-decay" min="1" placeholder="Decay (points per solve for linear, solves to reach minimum otherwise)" {}
+                                            button id="save-scoring" { "Save scoring" }
+                                        }
+                                    }
+                                    div class="action-btn-wrapper" {
+                                        button id="clear-scoring" { "Challenge uses the event scoring" }
+                                    }
+                                    h2 { "Blood bonus" }
+                                    p { "Current: " code { (blood_bonus) } }
+                                    div class="form-wrapper" {
+                                        form class="blood-bonus-form" method="post" {
+                                            select name="blood-bonus-kind" id="blood-bonus-kind" {
+                                                option value="points" { "Points" }
+                                                option value="percent" { "Percent of the challenge" }
+                                            }
+                                            input type="text" name="blood-bonuses" id="blood-bonuses" placeholder="First, second, third... e.g. 50, 30, 10" {}
+                                            button id="save-blood-bonus" { "Save blood bonus" }
+                                        }
+                                    }
+                                }
+                            } @else if path == MODULES_PATH {
+                                h1 id="section-title" { "Modules" }
+                                div class="section-wrapper" {
+                                    table class="the-table" {
+                                        tr {
+                                            th { "Module" }
+                                            th { "Status" }
+                                            th { "Restarts" }
+                                            th { "Running since" }
+                                            th { "Last crash" }
+                                        }
+                                        @for module in modules {
+                                            tr {
+                                                td { (module.name) }
+                                                @match module.status {
+                                                    ModuleStatus::Running => td { "🟢 running" },
+                                                    ModuleStatus::Restarting => td { "🟡 restarting" },
+                                                    ModuleStatus::Stopped => td { "🔴 stopped" }
+                                                }
+                                                td { (module.restarts) }
+                                                td { (DateTime::from_timestamp(module.running_since, 0).unwrap_or_default()) }
+                                                @match (module.last_crash_at, module.last_crash) {
+                                                    (Some(crash_time), Some(reason)) => td { (DateTime::from_timestamp(crash_time, 0).unwrap_or_default()) " - " (reason) },
+                                                    _ => td { "-" }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
@@ -456,8 +899,10 @@ pub async fn admin_index(page: web::Query<PaginationQuery>, db_conn: web::Data<D
 }
 
 
-pub async fn challenges(db_conn: web::Data<DbConnection>, req: HttpRequest) -> ActixResult<Markup> {
+pub async fn challenges(db_conn: web::Data<DbConnection>, scoreboard_cache: web::Data<ScoreboardCache>, req: HttpRequest) -> ActixResult<Markup> {
     let challs = db_conn.get_all_running_challenges().await;
+    let stages = utils::stages_by_challenge(db_conn.get_all_challenge_stages().await);
+    let bloods = db_conn.get_bloods().await;
 
     let cookie: Cookie<'_> = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
 
@@ -493,9 +938,10 @@ pub async fn challenges(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
                 div class="container" {
                     nav class="page-nav" {
                         a href="/scoreboard" { "Scoreboard" }
+                        a href="/team" { "Team" }
                         div { 
                             p { "Hello, "(username) }
-                            p {"Point: "(utils::get_user_score(db_conn.do_clone(), user_id).await)}
+                            p { "Point: " span id="user-score" { (scoreboard_cache.get().await.user_score(username)) } }
                         }
                     }
                     h1 style="margin-bottom: 20px;"{ "Challenges" }
@@ -519,6 +965,20 @@ pub async fn challenges(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
                                     p class="chall-score" { (chall.score) }
                                     p class="chall-category" { (chall.category) }
                                 }
+                                @if let Some(chall_stages) = stages.get(&chall.challenge_name) {
+                                    p class="chall-stages"
+                                        data-solved=(chall_stages.iter().filter(|stage| stage.solved_by.contains(username)).count())
+                                        data-total=(chall_stages.len()) {
+                                        (chall_stages.iter().filter(|stage| stage.solved_by.contains(username)).count())"/"(chall_stages.len())" stages"
+                                    }
+                                }
+                                @if bloods.iter().any(|blood| blood.challenge_name == chall.challenge_name) {
+                                    p class="chall-bloods" {
+                                        @for blood in bloods.iter().filter(|blood| blood.challenge_name == chall.challenge_name) {
+                                            span { (blood_badge(blood.position).unwrap_or_default())" "(blood.username) }
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
@@ -546,6 +1006,9 @@ pub async fn challenges(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
                                     div class="attachments" {
                                         a href="/attachments/aaaa.zip" id="attachment" { "V8.zip" }
                                     }
+
+                                    h4 id="attachment-label" { "Hints" }
+                                    div class="hints" id="hint-list" {}
                                 }
                             }
                         }
@@ -557,7 +1020,90 @@ pub async fn challenges(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
     ))
 }
 
-pub async fn scoreboard(db_conn: web::Data<DbConnection>, req: HttpRequest) -> ActixResult<Markup> {
+pub async fn team_page(db_conn: web::Data<DbConnection>, scoreboard_cache: web::Data<ScoreboardCache>, req: HttpRequest) -> ActixResult<Markup> {
+    let cookie: Cookie<'_> = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(html!(
+            script {
+                "location.href = '/login';"
+            }
+        ));
+    }
+
+    let no_username = String::from("");
+    let username = claims.get("username").unwrap_or(&no_username);
+    let user_id = claims.get("id").unwrap().parse::<i32>().unwrap_or(-1);
+
+    if user_id == -1 || username.is_empty() {
+        return Ok(html!());
+    }
+
+    let team = db_conn.get_team_of(username.to_string()).await;
+    let teammates = db_conn.get_teammates(username.to_string()).await;
+
+    Ok(html!(
+        html {
+            head {
+                link rel="stylesheet" href="/static/css/styles.css" {}
+                link rel="stylesheet" href="/static/css/styles_challenges.css" {}
+                meta charset="utf-8" {}
+                title {
+                    "CoSGang livectf - Team"
+                }
+            }
+            body {
+                div class="container" {
+                    nav class="page-nav" {
+                        a href="/challenges" { "Challenges" }
+                        a href="/scoreboard" { "Scoreboard" }
+                        div {
+                            p { "Hello, "(username) }
+                            p { "Point: "(scoreboard_cache.get().await.user_score(username)) }
+                        }
+                    }
+                    @if let Some(team) = team {
+                        h1 style="margin-bottom: 20px;" { (team.name) }
+                        div class="team-wrapper" {
+                            p { "Captain: "(team.captain) }
+                            p { "Invite code: " code id="invite-code" { (team.invite_code) } }
+                            h3 { "Members" }
+                            ul {
+                                @for teammate in teammates {
+                                    li {
+                                        (teammate)
+                                        @if team.captain == *username && teammate != *username {
+                                            " "
+                                            button class="kick-btn" data-username=(teammate) { "Kick" }
+                                        }
+                                    }
+                                }
+                            }
+                            @if team.captain == *username {
+                                button id="new-invite-code" { "New invite code" }
+                            }
+                            button id="leave-team" { "Leave team" }
+                        }
+                    } @else {
+                        h1 style="margin-bottom: 20px;" { "Team" }
+                        div class="team-wrapper" {
+                            p { "You are not in a team. Only players who haven't solved anything yet can join one." }
+                            input id="team-name" placeholder="Team name" {}
+                            button id="create-team" { "Create team" }
+                            input id="team-invite-code" placeholder="Invite code" {}
+                            button id="join-team" { "Join team" }
+                        }
+                    }
+                }
+            }
+            script src="/static/js/team.js" {}
+        }
+    ))
+}
+
+pub async fn scoreboard(scoreboard_cache: web::Data<ScoreboardCache>, req: HttpRequest) -> ActixResult<Markup> {
     let cookie: Cookie<'_> = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
 
     let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or(BTreeMap::new());
@@ -571,9 +1117,12 @@ pub async fn scoreboard(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
     }
 
     let username = claims.get("username").unwrap();
-    let user_id = claims.get("id").unwrap().parse::<i32>().unwrap_or(-1);
-    let users = db_conn.get_all_user().await;
-    let scoreboard_users = utils::get_scoreboard_from_user_vec(db_conn.do_clone(), users).await;
+    let scoreboard = scoreboard_cache.get().await;
+    let is_team_event = scoreboard.team_event;
+    let own_team = scoreboard.users.iter()
+        .find(|user| user.username == *username)
+        .and_then(|user| user.team.clone())
+        .unwrap_or(username.to_string());
     
     Ok(html!(
         html {
@@ -589,39 +1138,82 @@ pub async fn scoreboard(db_conn: web::Data<DbConnection>, req: HttpRequest) -> A
                 div class="container" {
                     nav class="page-nav" {
                         a href="/challenges" { "Challenges" }
+                        a href="/team" { "Team" }
                         div { 
                             p { "Hello, "(username) }
-                            p {"Point: "(utils::get_user_score(db_conn.do_clone(), user_id).await)}
+                            p {"Point: "(scoreboard.user_score(username))}
                         }
                     }
                     h1 style="margin-bottom: 20px;" { "Scoreboard" }
+                    div class="chart-wrapper" {
+                        canvas id="score-chart" width="1000" height="400" data-self=(if is_team_event { "" } else { username.as_str() }) {}
+                        div id="score-chart-legend" {}
+                    }
                     div class="wrapper" {
-                        table class="scoreboard" {
-                            tr {
-                                th class="place-col" { "Place" }
-                                th class="username-col" { "Username" }
-                                th class="score-col" { "Score" }
+                        @if is_team_event {
+                            table class="scoreboard" {
+                                tr {
+                                    th class="place-col" { "Place" }
+                                    th class="username-col" { "Team" }
+                                    th class="score-col" { "Score" }
+                                    th class="badges-col" { "Bloods" }
+                                }
+                                @for team in scoreboard.teams.iter() {
+                                    tr class=(if team.name == own_team { "is-self" } else { "" }) {
+                                        td class="place-col" { (team.place) }
+                                        td class="username-col" {
+                                            details {
+                                                summary { (team.name) }
+                                                @for member in team.members.iter() {
+                                                    p class="team-member" {
+                                                        (member.username)": "(member.score)
+                                                        @if !member.progress.is_empty() {
+                                                            " ("(member.progress.join(", "))")"
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        td class="score-col" { (team.score) }
+                                        td class="badges-col" { (team.badges.concat()) }
+                                    }
+                                }
                             }
-                            @for user in scoreboard_users {
-                                @if user.username == username.to_string() {
-                                    tr class="is-self" {
-                                        td class="place-col" { (user.place) }
-                                        td class="username-col" { (user.username) }
-                                        td class="score-col" { (user.score) }
-                                    }
-                                } @else {
-                                    tr {
-                                        td class="place-col" { (user.place) }
-                                        td class="username-col" { (user.username) }
-                                        td class="score-col" { (user.score) }
+                        } @else {
+                            table class="scoreboard" {
+                                tr {
+                                    th class="place-col" { "Place" }
+                                    th class="username-col" { "Username" }
+                                    th class="score-col" { "Score" }
+                                    th class="progress-col" { "In progress" }
+                                    th class="badges-col" { "Bloods" }
+                                }
+                                @for user in scoreboard.users.iter() {
+                                    @if user.username == *username {
+                                        tr class="is-self" {
+                                            td class="place-col" { (user.place) }
+                                            td class="username-col" { (user.username) }
+                                            td class="score-col" { (user.score) }
+                                            td class="progress-col" { (user.progress.join(", ")) }
+                                            td class="badges-col" { (user.badges.concat()) }
+                                        }
+                                    } @else {
+                                        tr {
+                                            td class="place-col" { (user.place) }
+                                            td class="username-col" { (user.username) }
+                                            td class="score-col" { (user.score) }
+                                            td class="progress-col" { (user.progress.join(", ")) }
+                                            td class="badges-col" { (user.badges.concat()) }
+                                        }
                                     }
                                 }
+
                             }
-                            
                         }
                     }
                 }
             }
+            script src="/static/js/scoreboard.js" {}
             script src="/static/js/challenges.js" {}
         }
     ))
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
+
+use crate::database::DbConnection;
+use crate::scoreboard_cache::ScoreboardCache;
+use crate::utils;
+use crate::web_interface::{get_jwt_claims, forbiden, get_error};
+
+const DEFAULT_HISTORY_TOP: usize = 10;
+const MAX_HISTORY_TOP: usize = 50;
+
+#[derive(serde::Deserialize)]
+pub struct HistoryQuery {
+    top: Option<usize>
+}
+
+// score series of the top players, or teams during a team event, in scoreboard order
+pub async fn api_score_history(db_conn: web::Data<DbConnection>, scoreboard_cache: web::Data<ScoreboardCache>, req: HttpRequest, query: web::Query<HistoryQuery>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let top = query.top.unwrap_or(DEFAULT_HISTORY_TOP).clamp(1, MAX_HISTORY_TOP);
+    let scoreboard = scoreboard_cache.get().await;
+
+    let groups: Vec<(String, Vec<String>)> = if scoreboard.team_event {
+        scoreboard.teams.iter()
+            .take(top)
+            .map(|team| (team.name.clone(), team.members.iter().map(|member| member.username.clone()).collect()))
+            .collect()
+    } else {
+        scoreboard.users.iter()
+            .take(top)
+            .map(|user| (user.username.clone(), vec![user.username.clone()]))
+            .collect()
+    };
+
+    Ok(HttpResponse::Ok().json(utils::get_score_history(&db_conn, groups).await))
+}
+
+pub async fn api_user_score_history(db_conn: web::Data<DbConnection>, req: HttpRequest, path: web::Path<(String,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let user = db_conn.get_user_by_name(path.0.clone()).await;
+    if user.id == -1 {
+        return Ok(get_error("User does not exist"));
+    }
+
+    let history = utils::get_score_history(&db_conn, vec![(user.username.clone(), vec![user.username])]).await;
+    Ok(HttpResponse::Ok().json(history.first()))
+}
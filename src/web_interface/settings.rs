@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
+
+use crate::database::{settings::{is_known_setting, BLOOD_BONUS_SETTING, EVENT_END_SETTING, SCORING_SETTING}, DbConnection};
+use crate::events::DomainEvent;
+use crate::notifier::NotifierComms;
+use crate::scoring::{BloodBonus, ScoringConfig};
+use crate::utils::MAGIC_TIME;
+use crate::web_interface::{get_jwt_claims, forbiden, unauthorized, get_error, success};
+
+// no challenge_name sets the event's scoring, a challenge without scoring goes back to the event's
+#[derive(serde::Deserialize)]
+pub struct ScoringUpdate {
+    challenge_name: Option<String>,
+    scoring: Option<ScoringConfig>
+}
+
+pub async fn api_settings_update(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, settings: web::Json<BTreeMap<String, i64>>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    if let Some(key) = settings.keys().find(|key| !is_known_setting(key)) {
+        return Ok(get_error(&format!("Unknown setting {}", key)));
+    }
+
+    if let Some(key) = settings.iter().find(|(_, value)| **value < 0).map(|(key, _)| key) {
+        return Ok(get_error(&format!("{} can't be negative", key)));
+    }
+
+    for (key, value) in settings.iter() {
+        let mut value = *value;
+        // times come from the browser like the challenge schedule, 0 clears them
+        if key == EVENT_END_SETTING && value > 0 {
+            value -= MAGIC_TIME as i64;
+        }
+
+        if !db_conn.set_setting(key, &value.to_string()).await {
+            return Ok(get_error(&format!("Can't save setting {}", key)));
+        }
+    }
+
+    // the team event setting decides what the scoreboard ranks
+    slaves.notify(DomainEvent::ScoreChanged { reason: "settings updated".to_string() });
+    Ok(success("Settings saved!"))
+}
+
+pub async fn api_scoring_update(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, update: web::Json<ScoringUpdate>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let update = update.into_inner();
+    if let Some(Err(err)) = update.scoring.as_ref().map(|scoring| scoring.strategy().validate()) {
+        return Ok(get_error(&format!("Invalid scoring: {}", err)));
+    }
+
+    let challenge_name = update.challenge_name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+    match challenge_name {
+        Some(challenge_name) => {
+            if !db_conn.set_challenge_scoring(challenge_name.clone(), update.scoring).await {
+                return Ok(get_error("Challenge does not exist"));
+            }
+            db_conn.recompute_challenge_score(challenge_name.clone()).await;
+            slaves.notify(DomainEvent::ScoreChanged { reason: format!("scoring of {} updated", challenge_name) });
+        },
+        None => {
+            let scoring = match update.scoring {
+                Some(scoring) => scoring,
+                None => return Ok(get_error("Missing scoring"))
+            };
+            if !db_conn.set_setting(SCORING_SETTING, &serde_json::to_string(&scoring).expect("failed serializing scoring")).await {
+                return Ok(get_error("Can't save scoring"));
+            }
+            db_conn.recompute_all_challenge_scores().await;
+            slaves.notify(DomainEvent::ScoreChanged { reason: "event scoring updated".to_string() });
+        }
+    }
+
+    Ok(success("Scoring saved!"))
+}
+
+pub async fn api_blood_bonus_update(db_conn: web::Data<DbConnection>, req: HttpRequest, blood_bonus: web::Json<BloodBonus>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    if let Err(err) = blood_bonus.validate() {
+        return Ok(get_error(&format!("Invalid blood bonus: {}", err)));
+    }
+
+    // only later solves get the new bonuses, recorded ones keep theirs
+    if !db_conn.set_setting(BLOOD_BONUS_SETTING, &serde_json::to_string(&blood_bonus.into_inner()).expect("failed serializing blood bonus")).await {
+        return Ok(get_error("Can't save blood bonus"));
+    }
+
+    Ok(success("Blood bonus saved!"))
+}
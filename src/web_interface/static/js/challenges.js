@@ -26,9 +26,62 @@ Array.from(challenges).map(chall => {
     modalAttachment.href = attachment;
     modalAttachment.textContent = `${challTitle}.tar.gz`
     modal.style.display = "block";
+    loadHints(challTitle);
   }
 })
 
+const loadHints = async (challTitle) => {
+  const hintList = document.querySelector("#hint-list");
+  hintList.replaceChildren();
+
+  const res = await fetch(`/api/challenges/${encodeURIComponent(challTitle)}/hints`, {
+    credentials: "include",
+    mode: "cors"
+  });
+  const hints = await res.json().catch(() => []);
+  if (!Array.isArray(hints) || hints.length === 0) {
+    hintList.textContent = "No hints";
+    return;
+  }
+
+  hints.map(hint => {
+    const item = document.createElement("p");
+    if (hint.unlocked) {
+      item.textContent = hint.content;
+    } else if (!hint.released) {
+      item.textContent = `Released at ${new Date(hint.release_at * 1000).toLocaleString()}`;
+    } else {
+      const unlock = document.createElement("button");
+      unlock.textContent = hint.cost > 0 ? `Unlock (-${hint.cost} points)` : "Unlock";
+      unlock.onclick = () => unlockHint(challTitle, hint);
+      item.appendChild(unlock);
+    }
+    hintList.appendChild(item);
+  });
+}
+
+const unlockHint = async (challTitle, hint) => {
+  if (hint.cost > 0 && !confirm(`This hint costs ${hint.cost} points, unlock it?`)) {
+    return;
+  }
+
+  const res = await fetch(`/api/hints/${hint.id}/unlock`, {
+    method: "POST",
+    credentials: "include",
+    mode: "cors"
+  });
+  const result = await res.json().catch(() => null);
+
+  if (result?.is_error !== false) {
+    alert(result?.message ?? "Hint could not be unlocked");
+    return;
+  }
+
+  const score = document.querySelector("#user-score");
+  score.textContent = Math.max(0, parseInt(score.textContent) - hint.cost);
+  loadHints(challTitle);
+}
+
 
 span.onclick = function() {
   modal.style.display = "none";
@@ -44,12 +97,39 @@ document.querySelector("#flag-submit").addEventListener("keyup", async (e) => {
   if (e.key === 'Enter' || e.keyCode === 13) {
     const flag = e.target.value;
 
-    await fetch(`/submit/${encodeURIComponent(flag)}`, {
+    const res = await fetch("/api/submit", {
       method: "POST",
       credentials: "include",
-      mode: "cors"
+      mode: "cors",
+      headers: {
+        "Content-Type": "application/json"
+      },
+      body: JSON.stringify({ flag })
     });
+    const result = await res.json().catch(() => null);
+
+    if (!result?.verdict) {
+      alert(result?.message ?? "Flag could not be checked");
+      return;
+    }
+
+    document.querySelector("#user-score").textContent = result.score;
+
+    const card = document.querySelector(`.challenge-wrapper[data-chall="${CSS.escape(result.verdict.challenge_name ?? "")}"]`);
+    if (result.verdict.verdict === "correct") {
+      card?.classList.add("challenge-solved");
+      e.target.value = "";
+    }
+
+    const stages = card?.querySelector(".chall-stages");
+    if (["stage_solved", "correct"].includes(result.verdict.verdict) && stages) {
+      const total = parseInt(stages.getAttribute("data-total"));
+      const solved = result.verdict.verdict === "correct" ? total : parseInt(stages.getAttribute("data-solved")) + 1;
+      stages.setAttribute("data-solved", solved);
+      stages.textContent = `${solved}/${total} stages`;
+      e.target.value = "";
+    }
 
-    alert("Flag is sent for checking");
+    alert(result.message);
   }
-});
\ No newline at end of file
+});
@@ -0,0 +1,89 @@
+const chart = document.querySelector("#score-chart");
+const legend = document.querySelector("#score-chart-legend");
+const COLORS = ["#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324", "#800000", "#000075"];
+const PADDING = 50;
+
+const fetchHistory = async (url) => {
+  const res = await fetch(url, {
+    method: "GET",
+    credentials: "include",
+    mode: "cors"
+  });
+  return await res.json().catch(() => null);
+}
+
+// every series is a step line from the first solve of anyone to now
+const drawChart = (series) => {
+  const ctx = chart.getContext("2d");
+  const points = series.flatMap(line => line.points);
+  ctx.clearRect(0, 0, chart.width, chart.height);
+  if (points.length === 0) {
+    ctx.fillText("No solves yet", chart.width / 2 - 30, chart.height / 2);
+    return;
+  }
+
+  const start = Math.min(...points.map(point => point.time));
+  const end = Math.max(Date.now() / 1000, ...points.map(point => point.time));
+  const maxScore = Math.max(1, ...points.map(point => point.score));
+  const x = (time) => PADDING + (time - start) / Math.max(1, end - start) * (chart.width - 2 * PADDING);
+  const y = (score) => chart.height - PADDING - score / maxScore * (chart.height - 2 * PADDING);
+
+  ctx.strokeStyle = "black";
+  ctx.fillStyle = "black";
+  ctx.beginPath();
+  ctx.moveTo(PADDING, PADDING);
+  ctx.lineTo(PADDING, chart.height - PADDING);
+  ctx.lineTo(chart.width - PADDING, chart.height - PADDING);
+  ctx.stroke();
+  ctx.fillText(maxScore, 5, PADDING);
+  ctx.fillText("0", 5, chart.height - PADDING);
+  ctx.fillText(new Date(start * 1000).toLocaleTimeString(), PADDING, chart.height - PADDING + 20);
+  ctx.fillText(new Date(end * 1000).toLocaleTimeString(), chart.width - 2 * PADDING, chart.height - PADDING + 20);
+
+  legend.innerHTML = "";
+  series.forEach((line, i) => {
+    const color = COLORS[i % COLORS.length];
+    ctx.strokeStyle = color;
+    ctx.lineWidth = line.self ? 3 : 1.5;
+    ctx.beginPath();
+    ctx.moveTo(x(start), y(0));
+    let score = 0;
+    line.points.forEach(point => {
+      ctx.lineTo(x(point.time), y(score));
+      score = point.score;
+      ctx.lineTo(x(point.time), y(score));
+    });
+    ctx.lineTo(x(end), y(score));
+    ctx.stroke();
+
+    const item = document.createElement("span");
+    item.style.borderColor = color;
+    item.textContent = `${line.name} (${score})`;
+    legend.appendChild(item);
+  });
+}
+
+const loadChart = async () => {
+  if (!chart) {
+    return;
+  }
+
+  const series = await fetchHistory("/api/scoreboard/history");
+  if (!Array.isArray(series)) {
+    return;
+  }
+
+  // players outside the top see their own line too
+  const self = chart.getAttribute("data-self");
+  if (self && !series.some(line => line.name === self)) {
+    const own = await fetchHistory(`/api/scoreboard/history/${encodeURIComponent(self)}`);
+    if (own?.points) {
+      series.push(own);
+    }
+  }
+  series.forEach(line => line.self = line.name === self);
+
+  drawChart(series);
+}
+
+loadChart();
@@ -23,7 +23,7 @@ Array.from(document.querySelectorAll(".ban-btn")).map(btn => {
     }
 })
 
-document.querySelector("#upload-challenge").addEventListener("click", async (e) => {
+document.querySelector("#upload-challenge")?.addEventListener("click", async (e) => {
     const data = new FormData(document.querySelector(".challenge-upload-form"));
 
     e.preventDefault();
@@ -55,7 +55,7 @@ document.querySelector("#upload-challenge").addEventListener("click", async (e)
     }
 })
 
-document.querySelector("#schedule-challenge").addEventListener("click", async (e) => {
+document.querySelector("#schedule-challenge")?.addEventListener("click", async (e) => {
     e.preventDefault();
     
     let challenge_name = document.querySelector("#challenge-name").value
@@ -85,7 +85,7 @@ document.querySelector("#schedule-challenge").addEventListener("click", async (e
     }
 });
 
-document.querySelector("#stop-btn").addEventListener("click", async (e) => {
+document.querySelector("#stop-btn")?.addEventListener("click", async (e) => {
     const challenge_name = e.target.getAttribute("data-challengeid");
     let res = await fetch(`/api/${challenge_name}/destroy`, {
         method: "POST",
@@ -100,4 +100,337 @@ document.querySelector("#stop-btn").addEventListener("click", async (e) => {
     } else {
         location.reload();
     }
-});
\ No newline at end of file
+});
+
+document.querySelector("#plan-rounds")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    let parsedStartTime = new Date(document.querySelector("#round-start-date").value + "T" + document.querySelector("#round-start-time").value + "Z");
+    parsedStartTime = Math.floor(parsedStartTime.getTime() / 1000);
+
+    const roundLength = parseInt(document.querySelector("#round-length").value) * 60;
+    const gap = parseInt(document.querySelector("#round-gap").value || "0") * 60;
+    const waves = document.querySelector("#round-waves").value
+        .split("\n")
+        .map(line => line.split(",").map(name => name.trim()).filter(name => name.length > 0))
+        .filter(wave => wave.length > 0);
+
+    let result = await fetch("/api/rounds", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            start_time: parsedStartTime,
+            round_length: roundLength,
+            gap: gap,
+            waves: waves
+        })
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+});
+
+Array.from(document.querySelectorAll("#pause-event, #resume-event")).map(btn => {
+    btn.onclick = async (e) => {
+        const action = e.target.id.replace("-event", "");
+        let res = await fetch(`/api/event/${action}`, {
+            method: "POST",
+            credentials: "include",
+            mode: "cors"
+        });
+
+        res = await res.json();
+        alert(res.message);
+    }
+})
+
+document.querySelector("#create-webhook")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    const topics = Array.from(document.querySelectorAll(".webhook-topic"))
+        .filter(checkbox => checkbox.checked)
+        .map(checkbox => checkbox.value);
+
+    let result = await fetch("/api/webhooks", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            url: document.querySelector("#webhook-url").value.trim(),
+            format: document.querySelector("#webhook-format").value,
+            template: document.querySelector("#webhook-template").value,
+            secret: document.querySelector("#webhook-secret").value,
+            topics: topics
+        })
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+});
+
+Array.from(document.querySelectorAll(".del-webhook-btn")).map(btn => {
+    btn.onclick = async (e) => {
+        const webhookId = e.target.getAttribute("data-webhookid");
+        let res = await fetch("/api/webhooks/"+webhookId, {
+            method: "DELETE",
+            credentials: "include",
+            mode: "cors"
+        });
+
+        res = await res.json();
+
+        if (res.is_error) {
+            alert(res.message);
+        } else {
+            location.reload();
+        }
+    }
+})
+
+Array.from(document.querySelectorAll(".test-webhook-btn")).map(btn => {
+    btn.onclick = async (e) => {
+        const webhookId = e.target.getAttribute("data-webhookid");
+        let res = await fetch(`/api/webhooks/${webhookId}/test`, {
+            method: "POST",
+            credentials: "include",
+            mode: "cors"
+        });
+
+        res = await res.json();
+        alert(res.message);
+        location.reload();
+    }
+})
+
+document.querySelector("#send-announcement")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    const message = document.querySelector("#announcement").value;
+
+    let res = await fetch("/api/announce", {
+        method: "POST",
+        credentials: "include",
+        mode: "cors",
+        headers: {
+            "Content-Type": "application/x-www-form-urlencoded; charset=UTF-8"
+        },
+        body: `message=${encodeURIComponent(message)}`
+    });
+
+    res = await res.json();
+    alert(res.message);
+});
+
+document.querySelector("#create-hint")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    let result = await fetch("/api/hints", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            challenge_name: document.querySelector("#hint-challenge").value.trim(),
+            content: document.querySelector("#hint-content").value,
+            cost: parseInt(document.querySelector("#hint-cost").value) || 0,
+            release_after: (parseInt(document.querySelector("#hint-release").value) || 0) * 60
+        })
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+});
+
+Array.from(document.querySelectorAll(".del-hint-btn")).map(btn => {
+    btn.onclick = async (e) => {
+        const hintId = e.target.getAttribute("data-hintid");
+        let res = await fetch("/api/hints/"+hintId, {
+            method: "DELETE",
+            credentials: "include",
+            mode: "cors"
+        });
+
+        res = await res.json();
+
+        if (res.is_error) {
+            alert(res.message);
+        } else {
+            location.reload();
+        }
+    }
+})
+
+Array.from(document.querySelectorAll(".del-team-btn")).map(btn => {
+    btn.onclick = async (e) => {
+        if (!confirm("Delete this team? Its members keep their solves.")) {
+            return;
+        }
+
+        const teamId = e.target.getAttribute("data-teamid");
+        let res = await fetch("/api/teams/"+teamId, {
+            method: "DELETE",
+            credentials: "include",
+            mode: "cors"
+        });
+
+        res = await res.json();
+
+        if (res.is_error) {
+            alert(res.message);
+        } else {
+            location.reload();
+        }
+    }
+})
+
+const saveSettings = async (settings) => {
+    let result = await fetch("/api/settings", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify(settings)
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+};
+
+document.querySelector("#save-settings")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    const settings = {};
+    Array.from(document.querySelectorAll(".setting-input")).map(input => {
+        settings[input.getAttribute("data-key")] = parseInt(input.value || "0");
+    });
+
+    // the event end stays as it is unless a new one is picked
+    const endDate = document.querySelector("#event-end-date").value;
+    const endTime = document.querySelector("#event-end-time").value;
+    if (endDate || endTime) {
+        let parsedEventEnd = new Date(endDate + "T" + endTime + "Z");
+        parsedEventEnd = Math.floor(parsedEventEnd.getTime() / 1000);
+
+        if (isNaN(parsedEventEnd)) {
+            alert("Please pick the end date and time");
+            return;
+        }
+        settings.event_end = parsedEventEnd;
+    }
+
+    await saveSettings(settings);
+});
+
+document.querySelector("#clear-event-end")?.addEventListener("click", async () => {
+    await saveSettings({ event_end: 0 });
+});
+
+const saveScoring = async (scoring) => {
+    let result = await fetch("/api/scoring", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            challenge_name: document.querySelector("#scoring-challenge").value.trim(),
+            scoring
+        })
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+};
+
+document.querySelector("#save-scoring")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    const kind = document.querySelector("#scoring-kind").value;
+    const initial = parseInt(document.querySelector("#scoring-initial").value || "0");
+    if (kind === "static") {
+        await saveScoring({ kind, points: initial });
+        return;
+    }
+
+    await saveScoring({
+        kind,
+        initial,
+        minimum: parseInt(document.querySelector("#scoring-minimum").value || "0"),
+        decay: parseInt(document.querySelector("#scoring-decay").value || "0")
+    });
+});
+
+document.querySelector("#save-blood-bonus")?.addEventListener("click", async (e) => {
+    e.preventDefault();
+
+    const bonuses = document.querySelector("#blood-bonuses").value
+        .split(",")
+        .map(bonus => bonus.trim())
+        .filter(bonus => bonus)
+        .map(bonus => parseInt(bonus));
+
+    if (bonuses.some(bonus => isNaN(bonus))) {
+        alert("Bonuses must be numbers separated by commas");
+        return;
+    }
+
+    let result = await fetch("/api/blood-bonus", {
+        method: "POST",
+        mode: "cors",
+        credentials: "include",
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            kind: document.querySelector("#blood-bonus-kind").value,
+            bonuses
+        })
+    });
+
+    result = await result.json();
+
+    alert(result.message);
+    if (!result.is_error) {
+        location.reload();
+    }
+});
+
+document.querySelector("#clear-scoring")?.addEventListener("click", async () => {
+    if (!document.querySelector("#scoring-challenge").value.trim()) {
+        alert("Please enter the challenge name");
+        return;
+    }
+    await saveScoring(null);
+});
@@ -0,0 +1,46 @@
+const teamRequest = async (url, body) => {
+  const res = await fetch(url, {
+    method: "POST",
+    credentials: "include",
+    mode: "cors",
+    headers: {
+      "Content-Type": "application/json"
+    },
+    body: JSON.stringify(body ?? {})
+  });
+  const result = await res.json().catch(() => null);
+
+  alert(result?.message ?? "Something went wrong");
+  if (result?.is_error === false) {
+    location.reload();
+  }
+}
+
+document.querySelector("#create-team")?.addEventListener("click", () => {
+  teamRequest("/api/teams", { name: document.querySelector("#team-name").value.trim() });
+});
+
+document.querySelector("#join-team")?.addEventListener("click", () => {
+  teamRequest("/api/teams/join", { invite_code: document.querySelector("#team-invite-code").value.trim() });
+});
+
+document.querySelector("#leave-team")?.addEventListener("click", () => {
+  if (confirm("Leave your team?")) {
+    teamRequest("/api/teams/leave");
+  }
+});
+
+document.querySelector("#new-invite-code")?.addEventListener("click", () => {
+  if (confirm("The current invite code will stop working, continue?")) {
+    teamRequest("/api/teams/invite");
+  }
+});
+
+Array.from(document.querySelectorAll(".kick-btn")).map(btn => {
+  btn.onclick = (e) => {
+    const username = e.target.getAttribute("data-username");
+    if (confirm(`Kick ${username}?`)) {
+      teamRequest("/api/teams/kick", { username });
+    }
+  }
+});
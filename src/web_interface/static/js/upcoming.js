@@ -0,0 +1,31 @@
+const releases = document.querySelectorAll(".release-wrapper");
+
+const pad = (value) => String(value).padStart(2, "0");
+
+const tick = () => {
+  const now = Math.floor(Date.now() / 1000);
+
+  Array.from(releases).map(release => {
+    const start = parseInt(release.getAttribute("data-start"));
+    const countdown = release.querySelector(".release-countdown");
+    let remaining = start - now;
+
+    if (remaining <= 0) {
+      release.classList.add("release-live");
+      countdown.textContent = "Live now!";
+      return;
+    }
+
+    const days = Math.floor(remaining / 86400);
+    remaining %= 86400;
+    const hours = Math.floor(remaining / 3600);
+    remaining %= 3600;
+    const minutes = Math.floor(remaining / 60);
+    const seconds = remaining % 60;
+
+    countdown.textContent = (days > 0 ? `${days}d ` : "") + `${pad(hours)}:${pad(minutes)}:${pad(seconds)}`;
+  })
+}
+
+tick();
+setInterval(tick, 1000);
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
+use uuid::Uuid;
+
+use crate::database::{settings::TEAM_SIZE_SETTING, team::{TeamCreate, TeamJoin, TeamLeave}, DbConnection};
+use crate::events::DomainEvent;
+use crate::notifier::NotifierComms;
+use crate::web_interface::{get_jwt_claims, forbiden, unauthorized, get_error, success};
+
+#[derive(serde::Deserialize)]
+pub struct TeamCreateData {
+    name: String
+}
+
+#[derive(serde::Deserialize)]
+pub struct TeamJoinData {
+    invite_code: String
+}
+
+#[derive(serde::Deserialize)]
+pub struct TeamKickData {
+    username: String
+}
+
+#[derive(serde::Serialize)]
+struct InviteCodeResponse {
+    is_error: bool,
+    message: String,
+    invite_code: String
+}
+
+fn new_invite_code() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+pub async fn api_team_create(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, form: web::Json<TeamCreateData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    if form.name.trim().is_empty() {
+        return Ok(get_error("Missing team name"));
+    }
+
+    if db_conn.get_team_of(username.clone()).await.is_some() {
+        return Ok(get_error("You are already in a team"));
+    }
+
+    match db_conn.create_team(form.name.clone(), username, new_invite_code()).await {
+        TeamCreate::Created(team) => {
+            slaves.notify(DomainEvent::ScoreChanged { reason: format!("team {} created", team.name) });
+            Ok(HttpResponse::Ok().json(InviteCodeResponse {
+                is_error: false,
+                message: "Team created!".to_string(),
+                invite_code: team.invite_code
+            }))
+        },
+        TeamCreate::NameTaken => Ok(get_error("Team name is taken")),
+        TeamCreate::AlreadyInTeam => Ok(get_error("You are already in a team")),
+        TeamCreate::HasSolves => Ok(get_error("Players who already solved something can't create a team"))
+    }
+}
+
+pub async fn api_team_join(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, form: web::Json<TeamJoinData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let size_limit = db_conn.get_settings().await.get(TEAM_SIZE_SETTING);
+    match db_conn.join_team(form.invite_code.clone(), username, size_limit).await {
+        TeamJoin::Joined(team) => {
+            slaves.notify(DomainEvent::ScoreChanged { reason: format!("a player joined team {}", team.name) });
+            Ok(success(&format!("You joined {}!", team.name)))
+        },
+        TeamJoin::NotFound => Ok(get_error("Invalid invite code")),
+        TeamJoin::Full => Ok(get_error("Team is full")),
+        TeamJoin::AlreadyInTeam => Ok(get_error("You are already in a team")),
+        TeamJoin::HasSolves => Ok(get_error("Players who already solved something can't join a team"))
+    }
+}
+
+pub async fn api_team_leave(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    // the team keeps whatever its members solved, so only players who haven't scored can go
+    match db_conn.leave_team(username).await {
+        TeamLeave::Left => {
+            slaves.notify(DomainEvent::ScoreChanged { reason: "a player left their team".to_string() });
+            Ok(success("You left your team"))
+        },
+        TeamLeave::NotInTeam => Ok(get_error("You are not in a team")),
+        TeamLeave::HasSolves => Ok(get_error("Players who already solved something can't leave their team"))
+    }
+}
+
+pub async fn api_team_kick(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, form: web::Json<TeamKickData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let team = match db_conn.get_team_of(username.clone()).await {
+        Some(team) if team.captain == username => team,
+        _ => return Ok(unauthorized("Only the captain can kick members"))
+    };
+
+    match db_conn.kick_team_member(&team, form.username.clone()).await {
+        TeamLeave::Left => {
+            slaves.notify(DomainEvent::ScoreChanged { reason: format!("a player was kicked from team {}", team.name) });
+            Ok(success(&format!("{} was kicked", form.username)))
+        },
+        TeamLeave::NotInTeam => Ok(get_error("Player is not a member of your team")),
+        TeamLeave::HasSolves => Ok(get_error("Players who already solved something can't be kicked"))
+    }
+}
+
+pub async fn api_team_invite(db_conn: web::Data<DbConnection>, req: HttpRequest) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+    let username = claims.get("username").cloned().unwrap_or_default();
+
+    if username.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let team = match db_conn.get_team_of(username.clone()).await {
+        Some(team) if team.captain == username => team,
+        _ => return Ok(unauthorized("Only the captain can change the invite code"))
+    };
+
+    // the old code stops working right away
+    let invite_code = new_invite_code();
+    if !db_conn.set_team_invite_code(team.id, invite_code.clone()).await {
+        return Ok(get_error("Can't change the invite code"));
+    }
+
+    Ok(HttpResponse::Ok().json(InviteCodeResponse {
+        is_error: false,
+        message: "New invite code created!".to_string(),
+        invite_code
+    }))
+}
+
+pub async fn api_team_delete(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    if !db_conn.delete_team(path.0).await {
+        return Ok(get_error("Team does not exist"));
+    }
+
+    slaves.notify(DomainEvent::ScoreChanged { reason: "a team was deleted".to_string() });
+    Ok(success("Team deleted!"))
+}
@@ -3,6 +3,8 @@ use std::collections::BTreeMap;
 
 use actix_web::{HttpResponse, web, http::header::ContentType, HttpRequest, cookie::Cookie};
 use crate::database::{DbConnection, user::UserInstance, DbFilter};
+use crate::events::DomainEvent;
+use crate::notifier::NotifierComms;
 use crate::web_interface::{JsonResponse, sign_jwt, get_jwt_claims, get_error, success, unauthorized, forbiden};
 
 #[derive(serde::Deserialize)]
@@ -34,7 +36,7 @@ pub async fn api_user_login(db_conn: web::Data<DbConnection>, form: web::Form<Lo
 
     if user.is_locked {
         let now = chrono::offset::Utc::now().timestamp();
-        if user.lock_due_at <= now {
+        if !user.is_locked_at(now) {
             user.unlock();
         } else {
 
@@ -51,7 +53,7 @@ pub async fn api_user_login(db_conn: web::Data<DbConnection>, form: web::Form<Lo
     return Ok(resp);
 }
 
-pub async fn api_user_register(db_conn: web::Data<DbConnection>, form: web::Form<RegisterData>) -> Result<HttpResponse, actix_web::Error> {
+pub async fn api_user_register(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, form: web::Form<RegisterData>) -> Result<HttpResponse, actix_web::Error> {
     if form.username.len() == 0 || form.password.len() == 0 || form.email.len() == 0 {
         return Ok(get_error("Missing username/password"));
     } 
@@ -62,6 +64,11 @@ pub async fn api_user_register(db_conn: web::Data<DbConnection>, form: web::Form
         return Ok(get_error("Invalid email"));
     }
 
+    // per-player flags of teams are derived from the team name, a player can't share it
+    if db_conn.get_all_teams().await.iter().any(|team| team.name == form.username.trim()) {
+        return Ok(get_error("Register failed"));
+    }
+
     let user = UserInstance::new(form.username.as_str(), form.password.as_str(), form.email.as_str(), false);
     
     let result = db_conn.user_register(user.censor_password(false)).await;
@@ -69,6 +76,8 @@ pub async fn api_user_register(db_conn: web::Data<DbConnection>, form: web::Form
     if !result {
         return Ok(get_error("Register failed"));
     } 
+
+    slaves.notify(DomainEvent::UserRegistered { username: form.username.clone() });
     
     let json_resp = JsonResponse {is_error: false, message: "Registered!".to_string()};
     let json_resp = serde_json::to_string(&json_resp).unwrap();
@@ -79,7 +88,7 @@ pub async fn api_user_register(db_conn: web::Data<DbConnection>, form: web::Form
     return Ok(resp);
 }
 
-pub async fn api_user_create(db_conn: web::Data<DbConnection>, req: HttpRequest, form: web::Form<UserInstance>) -> Result<HttpResponse, actix_web::Error> {
+pub async fn api_user_create(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, form: web::Form<UserInstance>) -> Result<HttpResponse, actix_web::Error> {
     let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
 
     let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or(BTreeMap::new());
@@ -105,6 +114,8 @@ pub async fn api_user_create(db_conn: web::Data<DbConnection>, req: HttpRequest,
     if !result {
         return Ok(get_error("Can't create user"));
     } 
+
+    slaves.notify(DomainEvent::UserRegistered { username: form.username.clone() });
     
     return Ok(success("User created!"));
 }
@@ -177,7 +188,7 @@ pub async fn api_get_user(db_conn: web::Data<DbConnection>, req: HttpRequest, pa
     return Ok(resp);
 }
 
-pub async fn api_delete_user(db_conn: web::Data<DbConnection>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+pub async fn api_delete_user(db_conn: web::Data<DbConnection>, slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
     let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
 
     let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or(BTreeMap::new());
@@ -197,7 +208,8 @@ pub async fn api_delete_user(db_conn: web::Data<DbConnection>, req: HttpRequest,
     if !result {
         return Ok(get_error("Can't delete user"));
     } 
-    
+
+    slaves.notify(DomainEvent::ScoreChanged { reason: "a user was deleted".to_string() });
     return Ok(success("User deleted!"));
 }
 
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, HttpRequest, cookie::Cookie};
+
+use crate::database::{webhook::WebhookData, DbConnection};
+use crate::events::DomainEvent;
+use crate::notifier::NotifierComms;
+use crate::webhook::{template::WebhookFormat, WebhookCommand, WEBHOOK_REQUEST_TIMEOUT, WEBHOOK_TOPICS};
+use crate::web_interface::{get_jwt_claims, forbiden, unauthorized, get_error, success};
+
+#[derive(serde::Deserialize)]
+pub struct AnnouncementData {
+    message: String
+}
+
+pub async fn api_webhook_create(db_conn: web::Data<DbConnection>, req: HttpRequest, webhook: web::Json<WebhookData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let webhook = webhook.into_inner();
+
+    if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+        return Ok(get_error("Webhook url must be http(s)"));
+    }
+
+    if WebhookFormat::parse(&webhook.format).is_none() {
+        return Ok(get_error("Unknown webhook format"));
+    }
+
+    if let Some(topic) = webhook.topics.iter().find(|topic| !WEBHOOK_TOPICS.contains(&topic.as_str())) {
+        return Ok(get_error(&format!("Unknown topic {}", topic)));
+    }
+
+    if !db_conn.create_webhook(webhook).await {
+        return Ok(get_error("Can't create webhook"));
+    }
+
+    Ok(success("Webhook created!"))
+}
+
+pub async fn api_webhook_delete(db_conn: web::Data<DbConnection>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    if !db_conn.delete_webhook(path.0).await {
+        return Ok(get_error("Webhook does not exist"));
+    }
+
+    Ok(success("Webhook deleted!"))
+}
+
+pub async fn api_webhook_test(slaves: web::Data<NotifierComms>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let webhook_id = path.0;
+    let result = slaves.request(|reply| WebhookCommand::Test { webhook_id, reply }, WEBHOOK_REQUEST_TIMEOUT).await;
+
+    match result {
+        Ok(Ok(msg)) => Ok(success(&msg)),
+        Ok(Err(msg)) => Ok(get_error(&msg)),
+        Err(err) => Ok(get_error(&format!("Webhook module failed: {}", err)))
+    }
+}
+
+pub async fn api_announce(slaves: web::Data<NotifierComms>, req: HttpRequest, form: web::Form<AnnouncementData>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = req.cookie("auth").unwrap_or(Cookie::build("auth", "").finish());
+
+    let claims: BTreeMap<String, String> = get_jwt_claims(cookie.value()).unwrap_or_default();
+
+    if claims.is_empty() {
+        return Ok(forbiden("Not authenticated"));
+    }
+
+    let is_admin = claims.get("is_admin").unwrap_or(&"false".to_string()).parse::<bool>().unwrap_or(false);
+    if !is_admin {
+        return Ok(unauthorized("You are not admin"));
+    }
+
+    let message = form.message.trim();
+    if message.is_empty() {
+        return Ok(get_error("Announcement is empty"));
+    }
+
+    slaves.notify(DomainEvent::Announcement { message: message.to_string() });
+
+    Ok(success("Announced!"))
+}
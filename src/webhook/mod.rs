@@ -0,0 +1,316 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::database::{webhook::{WebhookData, WebhookDelivery}, DbConnection};
+use crate::events::DomainEvent;
+use crate::module::Module;
+use crate::notifier::{NotifierMessage, Responder};
+
+pub mod template;
+
+use template::WebhookFormat;
+
+// events worth telling the outside world about
+pub const WEBHOOK_TOPICS: [&str; 4] = ["challenge_released", "challenge_retired", "first_blood", "announcement"];
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const WEBHOOK_MAX_ATTEMPTS: i32 = 3;
+// doubled after every failed attempt
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+// covers every retry of a test delivery
+pub const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum WebhookCommand {
+    // deliver a sample announcement to one webhook, answers with how it went
+    Test { webhook_id: i32, reply: Responder<Result<String, String>> }
+}
+
+pub(crate) struct WebhookCtx {
+    db_conn: DbConnection,
+    client: reqwest::Client,
+    retry_delay: Duration
+}
+
+impl WebhookCtx {
+    pub(crate) fn new(db_conn: DbConnection) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .expect("failed building webhook http client");
+
+        WebhookCtx::with_client(db_conn, client, WEBHOOK_RETRY_DELAY)
+    }
+
+    // lets deliveries go through another client, or retry without waiting
+    pub(crate) fn with_client(db_conn: DbConnection, client: reqwest::Client, retry_delay: Duration) -> Self {
+        WebhookCtx {
+            db_conn,
+            client,
+            retry_delay
+        }
+    }
+}
+
+impl Module for WebhookCtx {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn topics(&self) -> Vec<&'static str> {
+        let mut topics = WEBHOOK_TOPICS.to_vec();
+        topics.push("webhook");
+        topics
+    }
+
+    fn handle(self: Arc<Self>, message: NotifierMessage) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            match message {
+                NotifierMessage::Event(event) => {
+                    // webhooks are read on every event so admin changes apply right away
+                    for webhook in self.db_conn.get_webhooks_for_topic(event.topic()).await {
+                        let ctx = Arc::clone(&self);
+                        let event = event.clone();
+                        tokio::spawn(async move {
+                            deliver(&ctx, &webhook, &event).await;
+                        });
+                    }
+                },
+                NotifierMessage::Webhook(WebhookCommand::Test { webhook_id, reply }) => {
+                    tokio::spawn(async move {
+                        reply.respond(cmd_test(&self, webhook_id).await);
+                    });
+                },
+                other => println!("webhook ignoring message on {}", other.topic())
+            }
+        })
+    }
+}
+
+async fn cmd_test(ctx: &WebhookCtx, webhook_id: i32) -> Result<String, String> {
+    let webhook = match ctx.db_conn.get_webhook_by_id(webhook_id).await {
+        Some(webhook) => webhook,
+        None => return Err("Webhook does not exist".to_string())
+    };
+
+    let event = DomainEvent::Announcement { message: "This is a test delivery from livectf".to_string() };
+    let delivery = deliver(ctx, &webhook, &event).await;
+
+    if delivery.success {
+        Ok(format!("Delivered with status {}", delivery.status_code))
+    } else {
+        Err(format!("Delivery failed after {} attempts: {}", delivery.attempts, delivery.error))
+    }
+}
+
+// POST the rendered payload and record the outcome in the delivery log
+async fn deliver(ctx: &WebhookCtx, webhook: &WebhookData, event: &DomainEvent) -> WebhookDelivery {
+    let delivery = post_with_retries(&ctx.client, ctx.retry_delay, webhook, event).await;
+    ctx.db_conn.log_webhook_delivery(delivery.clone()).await;
+    delivery
+}
+
+// retries with backoff until the receiver answers with a success status
+async fn post_with_retries(client: &reqwest::Client, retry_delay: Duration, webhook: &WebhookData, event: &DomainEvent) -> WebhookDelivery {
+    let format = WebhookFormat::parse(&webhook.format).unwrap_or(WebhookFormat::Generic);
+    let body = template::render(format, &webhook.template, event);
+
+    let mut attempts = 0;
+    let mut status_code = 0;
+    let mut error = String::new();
+    let mut retry_delay = retry_delay;
+
+    while attempts < WEBHOOK_MAX_ATTEMPTS {
+        if attempts > 0 {
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        }
+        attempts += 1;
+
+        let timestamp = chrono::offset::Utc::now().timestamp().to_string();
+        let mut request = client.post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Livectf-Event", event.topic())
+            .header("X-Livectf-Timestamp", &timestamp);
+
+        if !webhook.secret.is_empty() {
+            request = request.header("X-Livectf-Signature", sign_payload(&webhook.secret, &timestamp, &body));
+        }
+
+        match request.body(body.clone()).send().await {
+            Ok(response) => {
+                status_code = response.status().as_u16() as i32;
+                if response.status().is_success() {
+                    error.clear();
+                    break;
+                }
+                error = format!("status {}", status_code);
+            },
+            Err(err) => {
+                status_code = 0;
+                error = err.to_string();
+            }
+        }
+
+        println!("webhook {} attempt {} failed: {}", webhook.id, attempts, error);
+    }
+
+    WebhookDelivery::new(webhook.id, event.topic().to_string(), attempts, status_code, error.is_empty(), error)
+}
+
+// receivers recompute HMAC-SHA256(secret, "<X-Livectf-Timestamp>.<body>") and compare,
+// the timestamp is signed too so an old delivery can't be replayed as new
+fn sign_payload(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::*;
+
+    fn webhook(url: String, secret: &str) -> WebhookData {
+        WebhookData {
+            id: 1,
+            url,
+            format: "discord".to_string(),
+            template: String::new(),
+            secret: secret.to_string(),
+            topics: vec![],
+            enabled: true
+        }
+    }
+
+    fn announcement() -> DomainEvent {
+        DomainEvent::Announcement { message: "hi".to_string() }
+    }
+
+    // answers the nth request with the nth status, every request it got ends up in the returned list
+    async fn serve(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let received = Arc::clone(&requests);
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0u8; 1024];
+                loop {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                    if read == 0 || is_complete(&request) {
+                        break;
+                    }
+                }
+                received.lock().unwrap().push(String::from_utf8_lossy(&request).to_string());
+
+                let response = format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn is_complete(request: &[u8]) -> bool {
+        let request = String::from_utf8_lossy(request);
+        let Some((head, body)) = request.split_once("\r\n\r\n") else {
+            return false;
+        };
+        let length = head.lines()
+            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+            .unwrap_or(0);
+        body.len() >= length
+    }
+
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines()
+            .find(|line| line.to_lowercase().starts_with(&format!("{}:", name.to_lowercase())))
+            .map(|line| line[name.len() + 1..].trim())
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_receiver_accepts() {
+        let (url, requests) = serve(vec![500, 502, 200]).await;
+
+        let delivery = post_with_retries(&reqwest::Client::new(), Duration::ZERO, &webhook(url, ""), &announcement()).await;
+
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status_code, 200);
+        assert_eq!(delivery.error, "");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let (url, requests) = serve(vec![503, 503, 503, 200]).await;
+
+        let delivery = post_with_retries(&reqwest::Client::new(), Duration::ZERO, &webhook(url, ""), &announcement()).await;
+
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, WEBHOOK_MAX_ATTEMPTS);
+        assert_eq!(delivery.status_code, 503);
+        assert_eq!(delivery.error, "status 503");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn unreachable_receivers_have_no_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let delivery = post_with_retries(&reqwest::Client::new(), Duration::ZERO, &webhook(url, ""), &announcement()).await;
+
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, WEBHOOK_MAX_ATTEMPTS);
+        assert_eq!(delivery.status_code, 0);
+        assert!(!delivery.error.is_empty());
+    }
+
+    #[tokio::test]
+    async fn signs_the_timestamp_and_body() {
+        let (url, requests) = serve(vec![200]).await;
+
+        post_with_retries(&reqwest::Client::new(), Duration::ZERO, &webhook(url, "secret"), &announcement()).await;
+
+        let request = requests.lock().unwrap()[0].clone();
+        let timestamp = header(&request, "X-Livectf-Timestamp").unwrap();
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        assert_eq!(header(&request, "X-Livectf-Event"), Some("announcement"));
+        assert_eq!(header(&request, "X-Livectf-Signature"), Some(sign_payload("secret", timestamp, body).as_str()));
+    }
+
+    #[tokio::test]
+    async fn unsigned_without_a_secret() {
+        let (url, requests) = serve(vec![200]).await;
+
+        post_with_retries(&reqwest::Client::new(), Duration::ZERO, &webhook(url, ""), &announcement()).await;
+
+        assert_eq!(header(&requests.lock().unwrap()[0], "X-Livectf-Signature"), None);
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256_of_timestamp_and_body() {
+        assert_eq!(
+            sign_payload("secret", "1700000000", r#"{"content":"hi"}"#),
+            "sha256=786cd668a72d88eb84d182ac85e334a27cf498b74f770467b2e0583cb80c4bcf"
+        );
+        assert_ne!(sign_payload("secret", "1700000001", r#"{"content":"hi"}"#), sign_payload("secret", "1700000000", r#"{"content":"hi"}"#));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::events::DomainEvent;
+
+// Payload rendering. Discord and Slack get a ready made message, generic webhooks get either
+// the event as JSON or a custom template where {{field}} placeholders are substituted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum WebhookFormat {
+    Discord,
+    Slack,
+    Generic
+}
+
+impl WebhookFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "discord" => Some(WebhookFormat::Discord),
+            "slack" => Some(WebhookFormat::Slack),
+            "generic" => Some(WebhookFormat::Generic),
+            _ => None
+        }
+    }
+}
+
+// human readable one-liner, used as the Discord/Slack message and as {{text}} in templates
+pub fn announcement_text(event: &DomainEvent) -> String {
+    match event {
+        DomainEvent::ChallengeReleased { challenge_name, connection_string } => format!("🚀 {} is out! Connect with `{}`", challenge_name, connection_string),
+        DomainEvent::ChallengeRetired { challenge_name } => format!("🏁 {} is closed", challenge_name),
+        DomainEvent::FlagAccepted { challenge_name, username } => format!("✅ {} solved {}", username, challenge_name),
+        DomainEvent::FirstBlood { challenge_name, username } => format!("🩸 First blood on {} by {}!", challenge_name, username),
+        DomainEvent::StageSolved { challenge_name, stage, username } => format!("🪜 {} cleared {} of {}", username, stage, challenge_name),
+        DomainEvent::HintUnlocked { challenge_name, username, cost } => format!("💡 {} bought a hint for {} ({} points)", username, challenge_name, cost),
+        DomainEvent::UserRegistered { username } => format!("👋 {} joined", username),
+        DomainEvent::DeployFailed { challenge_name, reason } => format!("⚠️ {} failed to deploy: {}", challenge_name, reason),
+        DomainEvent::Announcement { message } => format!("📢 {}", message),
+        DomainEvent::ScoreChanged { reason } => format!("📊 Scores changed: {}", reason),
+        DomainEvent::MessagesLost => "⚠️ A livectf process lost messages of the others".to_string()
+    }
+}
+
+fn event_fields(event: &DomainEvent) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("event", event.topic().to_string()),
+        ("text", announcement_text(event)),
+        ("time", chrono::offset::Utc::now().timestamp().to_string())
+    ];
+
+    match event {
+        DomainEvent::ChallengeReleased { challenge_name, connection_string } => {
+            fields.push(("challenge_name", challenge_name.clone()));
+            fields.push(("connection_string", connection_string.clone()));
+        },
+        DomainEvent::ChallengeRetired { challenge_name } => fields.push(("challenge_name", challenge_name.clone())),
+        DomainEvent::FlagAccepted { challenge_name, username } | DomainEvent::FirstBlood { challenge_name, username } => {
+            fields.push(("challenge_name", challenge_name.clone()));
+            fields.push(("username", username.clone()));
+        },
+        DomainEvent::StageSolved { challenge_name, stage, username } => {
+            fields.push(("challenge_name", challenge_name.clone()));
+            fields.push(("stage", stage.clone()));
+            fields.push(("username", username.clone()));
+        },
+        DomainEvent::HintUnlocked { challenge_name, username, cost } => {
+            fields.push(("challenge_name", challenge_name.clone()));
+            fields.push(("username", username.clone()));
+            fields.push(("cost", cost.to_string()));
+        },
+        DomainEvent::UserRegistered { username } => fields.push(("username", username.clone())),
+        DomainEvent::DeployFailed { challenge_name, reason } => {
+            fields.push(("challenge_name", challenge_name.clone()));
+            fields.push(("reason", reason.clone()));
+        },
+        DomainEvent::Announcement { message } => fields.push(("message", message.clone())),
+        DomainEvent::ScoreChanged { reason } => fields.push(("reason", reason.clone())),
+        DomainEvent::MessagesLost => {}
+    }
+
+    fields
+}
+
+pub fn render(format: WebhookFormat, template: &str, event: &DomainEvent) -> String {
+    match format {
+        WebhookFormat::Discord => serde_json::json!({ "content": announcement_text(event) }).to_string(),
+        WebhookFormat::Slack => serde_json::json!({ "text": announcement_text(event) }).to_string(),
+        WebhookFormat::Generic if template.trim().is_empty() => serde_json::json!({
+            "event": event.topic(),
+            "data": event
+        }).to_string(),
+        WebhookFormat::Generic => render_template(template, event)
+    }
+}
+
+// templates are JSON, so substituted values are escaped as JSON string content;
+// placeholders are meant to sit inside quotes: {"msg": "{{text}}"}
+fn render_template(template: &str, event: &DomainEvent) -> String {
+    let mut rendered = template.to_string();
+
+    for (name, value) in event_fields(event) {
+        let escaped = serde_json::to_string(&value).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), escaped);
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blood() -> DomainEvent {
+        DomainEvent::FirstBlood { challenge_name: "pwn1".to_string(), username: "a\"b\\c\nd".to_string() }
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert!(WebhookFormat::parse(" Discord ") == Some(WebhookFormat::Discord));
+        assert!(WebhookFormat::parse("slack") == Some(WebhookFormat::Slack));
+        assert!(WebhookFormat::parse("generic") == Some(WebhookFormat::Generic));
+        assert!(WebhookFormat::parse("teams").is_none());
+    }
+
+    #[test]
+    fn template_values_are_escaped_as_json() {
+        let rendered = render(WebhookFormat::Generic, r#"{"who": "{{username}}", "what": "{{challenge_name}}", "event": "{{event}}"}"#, &blood());
+
+        let json: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(json["who"], "a\"b\\c\nd");
+        assert_eq!(json["what"], "pwn1");
+        assert_eq!(json["event"], "first_blood");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_alone() {
+        let rendered = render(WebhookFormat::Generic, r#"{"x": "{{nope}}", "y": "{{reason}}"}"#, &blood());
+
+        assert_eq!(rendered, r#"{"x": "{{nope}}", "y": "{{reason}}"}"#);
+    }
+
+    #[test]
+    fn chat_formats_send_the_announcement_text() {
+        let discord: serde_json::Value = serde_json::from_str(&render(WebhookFormat::Discord, "ignored", &blood())).unwrap();
+        let slack: serde_json::Value = serde_json::from_str(&render(WebhookFormat::Slack, "", &blood())).unwrap();
+
+        assert_eq!(discord["content"], announcement_text(&blood()));
+        assert_eq!(slack["text"], announcement_text(&blood()));
+    }
+
+    #[test]
+    fn generic_without_template_sends_the_event() {
+        let json: serde_json::Value = serde_json::from_str(&render(WebhookFormat::Generic, "  ", &blood())).unwrap();
+
+        assert_eq!(json["event"], "first_blood");
+        assert_eq!(json["data"], serde_json::to_value(blood()).unwrap());
+    }
+}